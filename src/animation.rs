@@ -0,0 +1,233 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+
+use crate::clip::Rect;
+
+/// What a clip does after its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayMode {
+    /// Starts over from the first frame.
+    #[default]
+    Loop,
+    /// Plays backwards to the first frame, then forwards again.
+    PingPong,
+    /// Holds the last frame.
+    Once,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// Region of the sprite sheet.
+    pub source: Rect,
+    /// Seconds the frame stays up.
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub frames: Vec<Frame>,
+    pub mode: PlayMode,
+}
+
+impl Clip {
+    /// Slices equally sized frames out of a sheet, numbered left to right
+    /// and then top to bottom, each shown for `duration` seconds. Panics if
+    /// the frame size is zero.
+    pub fn from_sheet(
+        sheet: Rect,
+        (frame_width, frame_height): (usize, usize),
+        frames: impl IntoIterator<Item = usize>,
+        duration: f32,
+        mode: PlayMode,
+    ) -> Self {
+        assert!(frame_width > 0 && frame_height > 0, "sheet frames must be at least one pixel in size");
+        let columns = (sheet.width as usize / frame_width).max(1);
+        let frames = frames
+            .into_iter()
+            .map(|index| Frame {
+                source: Rect::new(
+                    sheet.x + ((index % columns) * frame_width) as i32,
+                    sheet.y + ((index / columns) * frame_height) as i32,
+                    frame_width as i32,
+                    frame_height as i32,
+                ),
+                duration,
+            })
+            .collect();
+        Self { frames, mode }
+    }
+
+    /// Overrides the frame durations in order; extra frames keep theirs.
+    pub fn with_durations(mut self, durations: &[f32]) -> Self {
+        for (frame, &duration) in self.frames.iter_mut().zip(durations) {
+            frame.duration = duration;
+        }
+        self
+    }
+
+    /// Frame indices of one full cycle; ping-pong doesn't repeat the ends.
+    fn sequence(&self) -> impl Iterator<Item = usize> + '_ {
+        let count = self.frames.len();
+        let back = match self.mode {
+            PlayMode::PingPong if count > 2 => 1..count - 1,
+            _ => 0..0,
+        };
+        (0..count).chain(back.rev())
+    }
+
+    /// Length of one cycle in seconds.
+    pub fn duration(&self) -> f32 {
+        self.sequence().map(|i| self.frames[i].duration).sum()
+    }
+
+    /// Index of the frame shown `time` seconds after the clip started.
+    pub fn frame_index(&self, time: f32) -> usize {
+        let cycle = self.duration();
+        if cycle <= 0.0 {
+            return 0;
+        }
+        let mut time = match self.mode {
+            PlayMode::Once => time.min(cycle),
+            PlayMode::Loop | PlayMode::PingPong => time.rem_euclid(cycle),
+        };
+        let mut last = 0;
+        for i in self.sequence() {
+            if time < self.frames[i].duration {
+                return i;
+            }
+            time -= self.frames[i].duration;
+            last = i;
+        }
+        last
+    }
+}
+
+/// Named clips over one sprite sheet and the playback position in the
+/// current clip. The sheet itself is passed in when drawing, so palette
+/// swapped copies can share an animation.
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    clips: HashMap<String, Clip>,
+    current: String,
+    time: f32,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces a clip. The first clip added starts playing.
+    pub fn add_clip(&mut self, name: impl Into<String>, clip: Clip) {
+        let name = name.into();
+        if self.clips.is_empty() {
+            self.current = name.clone();
+        }
+        self.clips.insert(name, clip);
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+
+    /// Name of the playing clip.
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Switches to another clip from its start. Playing the clip that is
+    /// already running keeps its position, so this can be called every
+    /// frame. Unknown names are ignored.
+    pub fn play(&mut self, name: &str) {
+        if self.current != name && self.clips.contains_key(name) {
+            self.current = name.to_owned();
+            self.time = 0.0;
+        }
+    }
+
+    /// Advances playback by `delta` seconds.
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+    }
+
+    /// Whether a `PlayMode::Once` clip has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.clips
+            .get(&self.current)
+            .is_some_and(|clip| clip.mode == PlayMode::Once && self.time >= clip.duration())
+    }
+
+    /// The sheet region to draw right now.
+    pub fn frame(&self) -> Option<Rect> {
+        let clip = self.clips.get(&self.current)?;
+        let frame = clip.frames.get(clip.frame_index(self.time))?;
+        Some(frame.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+    use crate::colors::Color;
+    use crate::sprite::{Blit, Sprite};
+
+    fn clip(mode: PlayMode) -> Clip {
+        Clip::from_sheet(Rect::new(0, 0, 32, 16), (16, 8), 0..4, 0.5, mode)
+    }
+
+    fn frames(clip: &Clip, times: &[f32]) -> Vec<usize> {
+        times.iter().map(|&t| clip.frame_index(t)).collect()
+    }
+
+    #[test]
+    fn frames_are_sliced_row_by_row() {
+        let clip = Clip::from_sheet(Rect::new(0, 4, 32, 16), (16, 8), [3, 0], 0.1, PlayMode::Loop);
+        let sources: Vec<_> = clip.frames.iter().map(|f| f.source).collect();
+        assert_eq!(sources, [Rect::new(16, 12, 16, 8), Rect::new(0, 4, 16, 8)]);
+    }
+
+    #[test]
+    fn play_modes_pick_frames() {
+        let times = [0.0, 0.6, 1.9, 2.1, 2.6, 3.1, 10.0];
+        assert_eq!(frames(&clip(PlayMode::Loop), &times), [0, 1, 3, 0, 1, 2, 0]);
+        assert_eq!(frames(&clip(PlayMode::PingPong), &times), [0, 1, 3, 2, 1, 0, 2]);
+        assert_eq!(frames(&clip(PlayMode::Once), &times), [0, 1, 3, 3, 3, 3, 3]);
+        let uneven = clip(PlayMode::Loop).with_durations(&[1.0, 0.0]);
+        assert_eq!((uneven.duration(), frames(&uneven, &[0.9, 1.0, 1.2])), (2.0, vec![0, 2, 2]));
+    }
+
+    #[test]
+    fn switching_clips_restarts_them() {
+        let mut animation = Animation::new();
+        animation.add_clip("run", clip(PlayMode::Loop));
+        animation.add_clip("jump", clip(PlayMode::Once));
+        assert_eq!(animation.current(), "run");
+        animation.update(0.6);
+        animation.play("run");
+        animation.play("swim");
+        assert_eq!((animation.current(), animation.frame()), ("run", Some(Rect::new(16, 0, 16, 8))));
+        animation.play("jump");
+        assert_eq!(animation.frame(), Some(Rect::new(0, 0, 16, 8)));
+        animation.update(1.9);
+        assert!(!animation.is_finished());
+        animation.update(0.2);
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn draws_the_current_frame() {
+        let sheet = Sprite::from_pixels(2, 1, vec![Color::RED.to_argb(), Color::GREEN.to_argb()]);
+        let mut animation = Animation::new();
+        animation.add_clip("blink", Clip::from_sheet(Rect::new(0, 0, 2, 1), (1, 1), 0..2, 0.5, PlayMode::Loop));
+        let mut canvas = Canvas::new(4, 4);
+        let draw = |canvas: &mut Canvas, animation: &Animation| {
+            canvas.draw_animation(&sheet, animation, (-1.0, 1.0), (1.0, -1.0), &Blit::default());
+            canvas.buffer().to_vec()
+        };
+        assert!(draw(&mut canvas, &animation).iter().all(|&p| p == 0xFF0000));
+        animation.update(0.75);
+        assert!(draw(&mut canvas, &animation).iter().all(|&p| p == 0x00FF00));
+    }
+}
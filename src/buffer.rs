@@ -1,12 +1,21 @@
 #![allow(unused)]
-use crate::Colors;
+use crate::blend::BlendMode;
+use crate::clip::{self, Rect};
+use crate::colors::Color;
+use crate::font::{self, Align, TextStyle};
+use crate::sprite::{Blit, Sprite, Transparency};
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 
+#[derive(Clone)]
 pub struct Buffer {
     buf: Vec<u32>,
     width: usize,
     height: usize,
-    color: u32,
+    color: Color,
+    blend_mode: BlendMode,
+    viewport: Rect,
+    clip_stack: Vec<Rect>,
+    depth: Option<Vec<f32>>,
 }
 
 impl Deref for Buffer {
@@ -50,7 +59,60 @@ impl Buffer {
             buf: vec![0; width * height],
             width,
             height,
-            color: Colors::WHITE,
+            color: Color::WHITE,
+            blend_mode: BlendMode::Normal,
+            viewport: Rect::new(0, 0, width as i32, height as i32),
+            clip_stack: Vec::new(),
+            depth: None,
+        }
+    }
+
+    /// Reallocates the pixels for a new size, clearing them and resetting the
+    /// viewport and clip stack. Color and blending settings are kept.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.buf = vec![0; width * height];
+        self.width = width;
+        self.height = height;
+        self.viewport = Rect::new(0, 0, width as i32, height as i32);
+        self.clip_stack.clear();
+        if self.depth.is_some() {
+            self.depth = Some(vec![f32::INFINITY; width * height]);
+        }
+    }
+
+    /// Allocates a depth buffer so that `raster_tri` hides occluded pixels.
+    pub fn enable_depth(&mut self) {
+        if self.depth.is_none() {
+            self.depth = Some(vec![f32::INFINITY; self.width * self.height]);
+        }
+    }
+
+    pub fn disable_depth(&mut self) {
+        self.depth = None;
+    }
+
+    pub fn has_depth(&self) -> bool {
+        self.depth.is_some()
+    }
+
+    pub fn clear_depth(&mut self) {
+        if let Some(depth) = &mut self.depth {
+            depth.fill(f32::INFINITY);
+        }
+    }
+
+    /// Whether something at `depth` would be in front of what is already
+    /// drawn. Always true without a depth buffer.
+    pub fn depth_test(&self, (x, y): (usize, usize), depth: f32) -> bool {
+        match &self.depth {
+            Some(buffer) => depth < buffer[y * self.width + x],
+            None => true,
+        }
+    }
+
+    pub fn write_depth(&mut self, (x, y): (usize, usize), depth: f32) {
+        if let Some(buffer) = &mut self.depth {
+            buffer[y * self.width + x] = depth;
         }
     }
 
@@ -58,68 +120,465 @@ impl Buffer {
         (self.width, self.height)
     }
 
-    pub fn color(&self) -> u32 {
+    pub fn color(&self) -> Color {
         self.color
     }
 
-    pub fn set_color(&mut self, color: u32) {
-        self.color = color;
+    /// Sets the drawing color, alpha included. Packed `0x00RRGGBB` values
+    /// are opaque.
+    pub fn set_color(&mut self, color: impl Into<Color>) {
+        self.color = color.into();
+    }
+
+    /// Changes only the alpha of the drawing color.
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.color.a = alpha;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// Sets the base clip rectangle that the clip stack is layered on. Set it
+    /// before pushing clips, since already pushed clips are not updated.
+    pub fn set_viewport(&mut self, rect: Rect) {
+        let bounds = Rect::new(0, 0, self.width as i32, self.height as i32);
+        self.viewport = bounds.intersect(&rect);
+    }
+
+    /// The area drawing is currently restricted to: the viewport intersected
+    /// with every rectangle on the clip stack.
+    pub fn clip_rect(&self) -> Rect {
+        self.clip_stack.last().copied().unwrap_or(self.viewport)
+    }
+
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = self.clip_rect().intersect(&rect);
+        self.clip_stack.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) -> Option<Rect> {
+        self.clip_stack.pop()
     }
 
+    /// Clears the pixels to black, and the depth buffer if there is one.
     pub fn clear(&mut self) {
-        self.iter_mut().for_each(|x| *x = 0);
+        self.buf.fill(0);
+        self.clear_depth();
     }
 
     pub fn fill(&mut self) {
-        let color = self.color;
-        self.iter_mut().for_each(|x| *x = color);
-    }
-
-    pub fn pixel(&mut self, index: (usize, usize)) {
-        self[index] = self.color;
-    }
-
-    pub fn line(&mut self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) {
-        let (x1, y1, x2, y2) = (x1 as i32, y1 as i32, x2 as i32, y2 as i32);
-        let dx = (x2 - x1).abs();
-        let sx = if x1 < x2 { 1 } else { -1 };
-        let dy = -(y2 - y1).abs();
-        let sy = if y1 < y2 { 1 } else { -1 };
-        let (mut x, mut y) = (x1, y1);
-        let mut error = dx + dy;
-        loop {
-            self.pixel((x as usize, y as usize));
-            if x == x2 && y == y2 {
-                break;
+        let rect = self.clip_rect();
+        for y in rect.y..rect.bottom() {
+            self.fill_span(y, rect.x, rect.right());
+        }
+    }
+
+    /// Blends the current color into pixels `start..end` of row `y`, clipped
+    /// to the clip rectangle. Runs of pixels are much faster than `pixel`
+    /// calls, see `BlendMode::blend_span`.
+    pub fn fill_span(&mut self, y: i32, start: i32, end: i32) {
+        let rect = self.clip_rect();
+        let (start, end) = (start.max(rect.x), end.min(rect.right()));
+        if y < rect.y || y >= rect.bottom() || start >= end {
+            return;
+        }
+        let row = y as usize * self.width;
+        let span = &mut self.buf[row + start as usize..row + end as usize];
+        self.blend_mode.blend_span(span, self.color);
+    }
+
+    /// Copies the pixels of `from` in `source` to `to` in this buffer, along
+    /// with their depths when both buffers have a depth buffer. Parts outside
+    /// either buffer are skipped; the clip rectangle is ignored.
+    pub fn copy_rect(&mut self, source: &Buffer, from: Rect, (x, y): (i32, i32)) {
+        let from = from.intersect(&Rect::new(0, 0, source.width as i32, source.height as i32));
+        let to = Rect::new(x, y, from.width, from.height).intersect(&Rect::new(0, 0, self.width as i32, self.height as i32));
+        if to.is_empty() {
+            return;
+        }
+        let (dx, dy) = (from.x - x, from.y - y);
+        for y in to.y..to.bottom() {
+            let src = (y + dy) as usize * source.width + (to.x + dx) as usize;
+            let dst = y as usize * self.width + to.x as usize;
+            let len = to.width as usize;
+            self.buf[dst..dst + len].copy_from_slice(&source.buf[src..src + len]);
+            if let (Some(depth), Some(source_depth)) = (&mut self.depth, &source.depth) {
+                depth[dst..dst + len].copy_from_slice(&source_depth[src..src + len]);
             }
+        }
+    }
+
+    /// Writes the current color to an in-bounds pixel using the blend mode.
+    fn plot(&mut self, x: usize, y: usize) {
+        let dst = self[(x, y)];
+        self[(x, y)] = self.blend_mode.blend(dst, self.color);
+    }
+
+    /// Sets a single pixel, silently skipping it if it falls outside the
+    /// current clip rectangle.
+    pub fn pixel(&mut self, (x, y): (i32, i32)) {
+        if self.clip_rect().contains((x, y)) {
+            self.plot(x as usize, y as usize);
+        }
+    }
+
+    /// Blends the current color into a pixel with its alpha scaled by
+    /// `coverage` in [0, 1], for anti-aliased edges. Pixels outside the clip
+    /// rectangle are skipped.
+    pub fn pixel_coverage(&mut self, (x, y): (i32, i32), coverage: f32) {
+        let alpha = (self.color.a as f32 * coverage.clamp(0.0, 1.0)).round() as u8;
+        if alpha == 0 || !self.clip_rect().contains((x, y)) {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let src = self.color.with_alpha(alpha);
+        self[(x, y)] = self.blend_mode.blend(self[(x, y)], src);
+    }
+
+    pub fn line(&mut self, p1: (i32, i32), p2: (i32, i32)) {
+        // Start part way along the full line rather than from its clipped
+        // ends, so clipping never moves the pixels that stay visible
+        let Some((first, last)) = clip::clip_line_steps(&self.clip_rect(), p1, p2) else {
+            return;
+        };
+        let dx = (p2.0 as i64 - p1.0 as i64).abs();
+        let sx = if p1.0 < p2.0 { 1 } else { -1 };
+        let dy = -(p2.1 as i64 - p1.1 as i64).abs();
+        let sy = if p1.1 < p2.1 { 1 } else { -1 };
+        let (mut x, mut y) = clip::line_pixel(p1, p2, first);
+        // The error the loop would have reached stepping here from `p1`
+        let (kx, ky) = ((x - p1.0 as i64).abs() as i128, (y - p1.1 as i64).abs() as i128);
+        let mut error = (dx as i128 * (ky + 1) + dy as i128 * (kx + 1)) as i64;
+        for _ in first..=last {
+            self.pixel((x as i32, y as i32));
             let e2 = 2 * error;
             if e2 >= dy {
-                if x == x2 {
-                    break;
-                }
                 error += dy;
                 x += sx;
             }
             if e2 <= dx {
-                if y == y2 {
-                    break;
-                }
                 error += dx;
                 y += sy;
             }
         }
     }
 
-    pub fn tri(&mut self, p1: (usize, usize), p2: (usize, usize), p3: (usize, usize)) {
+    pub fn tri(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32)) {
         self.line(p1, p2);
         self.line(p2, p3);
         self.line(p3, p1);
     }
 
-    pub fn sqr(&mut self, p1: (usize, usize), p2: (usize, usize), p3: (usize, usize), p4: (usize, usize)) {
+    pub fn sqr(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32), p4: (i32, i32)) {
         self.line(p1, p2);
         self.line(p2, p3);
         self.line(p3, p4);
         self.line(p4, p1);
     }
-}
\ No newline at end of file
+
+    pub fn fill_tri(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32)) {
+        self.fill_polygon(&[p1, p2, p3]);
+    }
+
+    pub fn fill_quad(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32), p4: (i32, i32)) {
+        self.fill_polygon(&[p1, p2, p3, p4]);
+    }
+
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)]) {
+        let points: Vec<_> = points.iter().map(|&(x, y)| (x as f32, y as f32)).collect();
+        self.fill_polygon_f32(&points);
+    }
+
+    /// Fills a polygon with sub-pixel corners, in pixel units where pixel
+    /// `(x, y)` covers `[x, x + 1) x [y, y + 1)`.
+    pub fn fill_polygon_f32(&mut self, points: &[(f32, f32)]) {
+        scan_polygon(&self.clip_rect(), points, |y, start, end| self.fill_span(y, start, end));
+    }
+
+    /// Draws `sprite` stretched (nearest neighbor) to cover `dest`. With
+    /// `Transparency::Alpha` the sprite's alpha is scaled by the buffer's
+    /// current alpha and drawn with its blend mode, so sprites can fade;
+    /// the other modes copy the texels they draw as-is.
+    pub fn blit(&mut self, sprite: &Sprite, dest: Rect, blit: &Blit) {
+        let source = blit.source.unwrap_or(sprite.bounds()).intersect(&sprite.bounds());
+        if source.is_empty() || dest.is_empty() {
+            return;
+        }
+        let visible = self.clip_rect().intersect(&dest);
+        for y in visible.y..visible.bottom() {
+            let mut v = (y - dest.y) as i64 * source.height as i64 / dest.height as i64;
+            if blit.flip_y {
+                v = source.height as i64 - 1 - v;
+            }
+            for x in visible.x..visible.right() {
+                let mut u = (x - dest.x) as i64 * source.width as i64 / dest.width as i64;
+                if blit.flip_x {
+                    u = source.width as i64 - 1 - u;
+                }
+                let texel = sprite.get((source.x as i64 + u) as usize, (source.y as i64 + v) as usize);
+                let (x, y) = (x as usize, y as usize);
+                match blit.transparency {
+                    Transparency::Alpha => {
+                        let alpha = texel.a as u32 * self.color.a as u32 / 255;
+                        let src = texel.with_alpha(alpha as u8);
+                        self[(x, y)] = self.blend_mode.blend(self[(x, y)], src);
+                    }
+                    Transparency::ColorKey(key) if texel.to_u32() == key.to_u32() => {}
+                    Transparency::ColorKey(_) | Transparency::Opaque => self[(x, y)] = texel.to_u32(),
+                }
+            }
+        }
+    }
+
+    /// Draws `text` with the built-in bitmap font. `pos` is the top of the
+    /// first line, at its left edge, center or right edge depending on the
+    /// alignment; each line of a multi-line string is aligned on its own.
+    pub fn text(&mut self, (x, y): (i32, i32), text: &str, style: &TextStyle) {
+        let previous = self.color;
+        if let Some(color) = style.color {
+            self.set_color(color);
+        }
+        let scale = style.scale.max(1) as i32;
+        for (row, line) in text.split('\n').enumerate() {
+            let width = font::line_width(line) as i32 * scale;
+            let mut pen_x = match style.align {
+                Align::Left => x,
+                Align::Center => x - width / 2,
+                Align::Right => x - width,
+            };
+            let pen_y = y + (row * font::LINE_HEIGHT) as i32 * scale;
+            for c in line.chars() {
+                for (column, bits) in font::glyph(c).iter().enumerate() {
+                    for bit in 0..font::GLYPH_HEIGHT {
+                        if bits & (1 << bit) == 0 {
+                            continue;
+                        }
+                        let left = pen_x + column as i32 * scale;
+                        let top = pen_y + bit as i32 * scale;
+                        for py in top..top + scale {
+                            for px in left..left + scale {
+                                self.pixel((px, py));
+                            }
+                        }
+                    }
+                }
+                pen_x += font::ADVANCE as i32 * scale;
+            }
+        }
+        self.set_color(previous);
+    }
+}
+
+/// Scanline conversion using the even-odd rule, calling `span(y, start, end)`
+/// for each half-open run of pixels inside the polygon and `rect`. Pixels are
+/// sampled at their centers (top-left fill rule), so polygons sharing an edge
+/// never cover the same pixel twice or leave a gap.
+pub fn scan_polygon(rect: &Rect, points: &[(f32, f32)], mut span: impl FnMut(i32, i32, i32)) {
+    // Rows and spans are clamped to `rect` rather than clipping the polygon
+    // first, since the corners clipping adds would move the edges
+    if points.len() < 3 {
+        return;
+    }
+    let min_y = points.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = points.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+    let start_y = ((min_y - 0.5).ceil() as i32).max(rect.y);
+    let end_y = ((max_y - 0.5).ceil() as i32).min(rect.bottom());
+    let mut crossings = Vec::new();
+    for y in start_y..end_y {
+        let sample_y = y as f32 + 0.5;
+        crossings.clear();
+        for i in 0..points.len() {
+            // Edges always run downwards, so an edge shared by two polygons
+            // crosses each row at exactly the same x whichever way they store it
+            let (mut p1, mut p2) = (points[i], points[(i + 1) % points.len()]);
+            if p1.1 > p2.1 {
+                std::mem::swap(&mut p1, &mut p2);
+            }
+            let ((x1, y1), (x2, y2)) = (p1, p2);
+            if y1 <= sample_y && sample_y < y2 {
+                crossings.push(x1 + (sample_y - y1) / (y2 - y1) * (x2 - x1));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks_exact(2) {
+            let start = ((pair[0] - 0.5).ceil() as i32).max(rect.x);
+            let end = ((pair[1] - 0.5).ceil() as i32).min(rect.right());
+            if start < end {
+                span(y, start, end);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Indices of the pixels a drawing sets on a black 12x12 buffer.
+    fn painted(draw: impl FnOnce(&mut Buffer)) -> Vec<usize> {
+        let mut buffer = Buffer::new(12, 12);
+        buffer.set_color(0xFFFFFF);
+        draw(&mut buffer);
+        buffer.iter().enumerate().filter(|(_, &p)| p != 0).map(|(i, _)| i).collect()
+    }
+
+    /// Checks that `parts` cover exactly `whole`, with no pixel painted by
+    /// two of them.
+    fn assert_tiles(parts: &[Vec<usize>], whole: &[usize]) {
+        let mut union: Vec<usize> = parts.concat();
+        union.sort_unstable();
+        let count = union.len();
+        union.dedup();
+        assert_eq!(union.len(), count, "shared edges were painted twice");
+        assert_eq!(union, whole, "shared edges left gaps");
+    }
+
+    #[test]
+    fn clipped_lines_keep_the_pixels_of_the_whole_line() {
+        let clip = Rect::new(3, 2, 6, 7);
+        let border: Vec<(i32, i32)> = (0..12).flat_map(|i| [(i, 0), (i, 11), (0, i), (11, i)]).collect();
+        for &p1 in &border {
+            for &p2 in &border {
+                let whole = painted(|b| b.line(p1, p2));
+                let clipped = painted(|b| {
+                    b.push_clip(clip);
+                    b.line(p1, p2);
+                });
+                let inside: Vec<usize> =
+                    whole.into_iter().filter(|&i| clip.contains(((i % 12) as i32, (i / 12) as i32))).collect();
+                assert_eq!(clipped, inside, "{p1:?} to {p2:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn clipped_polygons_keep_the_pixels_of_the_whole_polygon() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let draw = |clip: Option<Rect>, points: &[(f32, f32)]| {
+            let mut buffer = Buffer::new(97, 61);
+            buffer.set_color(0xFFFFFF);
+            buffer.set_viewport(Rect::new(18, 0, 61, 61));
+            if let Some(clip) = clip {
+                buffer.push_clip(clip);
+            }
+            buffer.fill_polygon_f32(points);
+            buffer
+        };
+        let check = |clip: Rect, points: &[(f32, f32)]| {
+            let whole = draw(None, points);
+            let clipped = draw(Some(clip), points);
+            let clip = whole.viewport().intersect(&clip);
+            for y in 0..61 {
+                for x in 0..97 {
+                    let expected = if clip.contains((x, y)) { whole[(x as usize, y as usize)] } else { 0 };
+                    assert_eq!(clipped[(x as usize, y as usize)], expected, "{points:?} in {clip:?} at {x}, {y}");
+                }
+            }
+        };
+        check(Rect::new(33, 0, 31, 61), &[(62.0, 54.0), (78.0, 57.0), (13.0, 51.0)]);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..500 {
+            let clip = Rect::new(rng.gen_range(0..90), rng.gen_range(0..55), rng.gen_range(0..40), rng.gen_range(0..40));
+            let count = rng.gen_range(3..7);
+            let points: Vec<_> = (0..count).map(|_| (rng.gen_range(-20.0..117.0), rng.gen_range(-20.0..81.0))).collect();
+            check(clip, &points);
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_an_edge_tile_their_quad() {
+        let square = painted(|b| b.fill_quad((1, 1), (10, 1), (10, 9), (1, 9)));
+        assert_eq!(square.len(), 9 * 8);
+        let halves = [
+            painted(|b| b.fill_tri((1, 1), (10, 1), (10, 9))),
+            painted(|b| b.fill_tri((1, 1), (10, 9), (1, 9))),
+        ];
+        assert_tiles(&halves, &square);
+    }
+
+    #[test]
+    fn quads_sharing_an_edge_tile_their_union() {
+        let whole = painted(|b| b.fill_quad((1, 2), (11, 0), (11, 11), (1, 9)));
+        let parts = [
+            painted(|b| b.fill_quad((1, 2), (6, 1), (6, 10), (1, 9))),
+            painted(|b| b.fill_quad((6, 1), (11, 0), (11, 11), (6, 10))),
+        ];
+        assert_tiles(&parts, &whole);
+    }
+
+    #[test]
+    fn polygon_fan_tiles_the_polygon() {
+        let outline = [(6, 0), (11, 4), (9, 11), (3, 11), (0, 4)];
+        let whole = painted(|b| b.fill_polygon(&outline));
+        let center = (6, 6);
+        let fan: Vec<_> = (0..outline.len())
+            .map(|i| painted(|b| b.fill_tri(center, outline[i], outline[(i + 1) % outline.len()])))
+            .collect();
+        assert_tiles(&fan, &whole);
+    }
+
+    #[test]
+    fn random_quads_split_on_a_diagonal_tile_the_quad() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // Drawn additively, a pixel painted twice comes out brighter
+        let additive = |draw: &dyn Fn(&mut Buffer)| {
+            let mut buffer = Buffer::new(24, 24);
+            buffer.set_color(Color::rgb(1, 1, 1));
+            buffer.set_blend_mode(BlendMode::Additive);
+            draw(&mut buffer);
+            buffer
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut quads = 0;
+        while quads < 2000 {
+            let [a, b, c, d] = [(); 4].map(|_| (rng.gen_range(0..24), rng.gen_range(0..24)));
+            let cross = |o: (i32, i32), p: (i32, i32), q: (i32, i32)| (p.0 - o.0) * (q.1 - o.1) - (p.1 - o.1) * (q.0 - o.0);
+            let turns = [cross(a, b, c), cross(b, c, d), cross(c, d, a), cross(d, a, b)];
+            if !(turns.iter().all(|&t| t > 0) || turns.iter().all(|&t| t < 0)) {
+                continue;
+            }
+            quads += 1;
+            let whole = additive(&|buffer| buffer.fill_quad(a, b, c, d));
+            let halves = additive(&|buffer| {
+                buffer.fill_tri(a, b, c);
+                buffer.fill_tri(c, d, a);
+            });
+            assert!(*halves == *whole, "{a:?} {b:?} {c:?} {d:?}");
+        }
+    }
+
+    #[test]
+    fn spans_are_clipped_and_blend_like_pixels() {
+        let mut spans = Buffer::new(12, 4);
+        let mut pixels = Buffer::new(12, 4);
+        for buffer in [&mut spans, &mut pixels] {
+            buffer.set_color(Color::rgb(10, 20, 30));
+            buffer.fill();
+            buffer.set_color(Color::rgba(200, 100, 50, 90));
+            buffer.push_clip(Rect::new(2, 1, 8, 2));
+        }
+        spans.fill_span(1, -5, 20);
+        spans.fill_span(2, 4, 7);
+        spans.fill_span(3, 0, 12);
+        spans.fill_span(2, 9, 3);
+        for x in 2..10 {
+            pixels.pixel((x, 1));
+        }
+        for x in 4..7 {
+            pixels.pixel((x, 2));
+        }
+        assert!(*spans == *pixels);
+        assert_eq!(spans[(1, 1)], 0x0A141E);
+    }
+}
@@ -0,0 +1,115 @@
+#![allow(unused)]
+
+use crate::clip::Rect;
+use crate::linalg::vec2::Vec2;
+
+/// Maps world coordinates (y up) onto the screen while keeping their aspect
+/// ratio. The visible area is fitted inside the screen, leaving black bars
+/// above and below (letterbox) or left and right (pillarbox) as needed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    /// World point shown at the center of the viewport.
+    pub position: Vec2<f32>,
+    /// Magnification; 2.0 shows half as much of the world in each direction.
+    pub zoom: f32,
+    /// Size of the visible world area, in world units, at zoom 1.
+    pub view_size: Vec2<f32>,
+}
+
+impl Camera2D {
+    pub fn new(view_width: f32, view_height: f32) -> Self {
+        Self {
+            position: Vec2::zero(),
+            zoom: 1.0,
+            view_size: Vec2::new(view_width, view_height),
+        }
+    }
+
+    /// The largest screen rectangle with the view's aspect ratio, centered.
+    pub fn viewport(&self, (width, height): (usize, usize)) -> Rect {
+        let aspect = self.view_size.x / self.view_size.y;
+        let (width, height) = (width as f32, height as f32);
+        let (w, h) = if width / height > aspect {
+            (height * aspect, height)
+        } else {
+            (width, width / aspect)
+        };
+        Rect::new(
+            ((width - w) / 2.0).round() as i32,
+            ((height - h) / 2.0).round() as i32,
+            w.round() as i32,
+            h.round() as i32,
+        )
+    }
+
+    /// Screen pixels per world unit.
+    fn pixels_per_unit(&self, viewport: &Rect) -> f32 {
+        viewport.width as f32 * self.zoom / self.view_size.x
+    }
+
+    fn center(viewport: &Rect) -> Vec2<f32> {
+        Vec2::new(
+            viewport.x as f32 + viewport.width as f32 / 2.0,
+            viewport.y as f32 + viewport.height as f32 / 2.0,
+        )
+    }
+
+    pub fn world_to_screen(&self, p: Vec2<f32>, screen: (usize, usize)) -> Vec2<f32> {
+        let viewport = self.viewport(screen);
+        let scale = self.pixels_per_unit(&viewport);
+        let offset = (p - self.position) * scale;
+        Self::center(&viewport) + Vec2::new(offset.x, -offset.y)
+    }
+
+    pub fn screen_to_world(&self, p: Vec2<f32>, screen: (usize, usize)) -> Vec2<f32> {
+        let viewport = self.viewport(screen);
+        let scale = self.pixels_per_unit(&viewport);
+        let offset = (p - Self::center(&viewport)) / scale;
+        self.position + Vec2::new(offset.x, -offset.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+
+    #[test]
+    fn viewport_keeps_the_aspect_ratio() {
+        let camera = Camera2D::new(16.0, 9.0);
+        assert_eq!(camera.viewport((160, 90)), Rect::new(0, 0, 160, 90));
+        assert_eq!(camera.viewport((160, 120)), Rect::new(0, 15, 160, 90));
+        assert_eq!(camera.viewport((200, 90)), Rect::new(20, 0, 160, 90));
+    }
+
+    #[test]
+    fn world_and_screen_round_trip() {
+        let mut camera = Camera2D::new(16.0, 9.0);
+        camera.position = Vec2::new(2.0, 1.0);
+        camera.zoom = 2.0;
+        let screen = (200, 90);
+        assert_eq!(camera.world_to_screen(camera.position, screen), Vec2::new(100.0, 45.0));
+        let corner = camera.world_to_screen(Vec2::new(6.0, 3.25), screen);
+        assert_eq!(corner, Vec2::new(180.0, 0.0));
+        let back = camera.screen_to_world(corner, screen);
+        assert!((back.x - 6.0).abs() < 1e-5 && (back.y - 3.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn canvas_draws_only_inside_the_viewport() {
+        let mut canvas = Canvas::new(40, 20);
+        canvas.set_camera(Some(Camera2D::new(2.0, 2.0)));
+        canvas.set_color(0xFFFFFF);
+        canvas.fill();
+        let pixels = canvas.buffer();
+        let lit: Vec<_> = (0..pixels.len()).filter(|&i| pixels[i] != 0).map(|i| (i % 40, i / 40)).collect();
+        assert_eq!(lit.len(), 20 * 20);
+        assert!(lit.iter().all(|&(x, _)| (10..30).contains(&x)));
+        canvas.resize(20, 40);
+        canvas.fill();
+        let pixels = canvas.buffer();
+        let lit: Vec<_> = (0..pixels.len()).filter(|&i| pixels[i] != 0).map(|i| (i % 20, i / 20)).collect();
+        assert_eq!(lit.len(), 20 * 20);
+        assert!(lit.iter().all(|&(_, y)| (10..30).contains(&y)));
+    }
+}
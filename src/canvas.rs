@@ -1,17 +1,101 @@
 #![allow(unused)]
 
+use std::f32::consts::TAU;
+use std::io;
 use std::ops::{Deref, DerefMut};
+use std::path::Path;
 
-use crate::{buffer::Buffer, colors::Colors};
+use crate::{
+    animation::Animation,
+    blend::BlendMode,
+    buffer::Buffer,
+    camera::Camera2D,
+    clip::Rect,
+    colors::Color,
+    display::{Command, DisplayList, Shape},
+    font::{self, Align, TextStyle},
+    linalg::{matrix::Mat3x3, vec2::Vec2},
+    mesh::Mesh,
+    pipeline::Pipeline,
+    shapes,
+    sprite::{Blit, Sprite},
+    stroke::StrokeStyle,
+    tilemap::{TileLook, Tilemap},
+    tiles::TileRenderer,
+};
 
 pub struct Canvas {
     buf: Buffer,
+    text_style: TextStyle,
+    stroke_style: StrokeStyle,
+    transform: Mat3x3<f32>,
+    transform_stack: Vec<Mat3x3<f32>>,
+    camera: Option<Camera2D>,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
         Canvas {
             buf: Buffer::new(width, height),
+            text_style: TextStyle::default(),
+            stroke_style: StrokeStyle::default(),
+            transform: Mat3x3::identity(),
+            transform_stack: Vec::new(),
+            camera: None,
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.buf.size()
+    }
+
+    /// Resizes the backing buffer, e.g. after the window was resized. The
+    /// contents are cleared and clips are dropped.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.buf.resize(width, height);
+        self.set_camera(self.camera);
+    }
+
+    pub fn camera(&self) -> Option<Camera2D> {
+        self.camera
+    }
+
+    /// With a camera, coordinates are world units and drawing is confined to
+    /// the camera's viewport. Without one, [-1, 1] is stretched over the
+    /// whole buffer.
+    pub fn set_camera(&mut self, camera: Option<Camera2D>) {
+        let (width, height) = self.buf.size();
+        let viewport = match camera {
+            Some(camera) => camera.viewport((width, height)),
+            None => Rect::new(0, 0, width as i32, height as i32),
+        };
+        self.camera = camera;
+        self.buf.set_viewport(viewport);
+    }
+
+    /// Converts a pixel position into world coordinates (or normalized
+    /// coordinates without a camera), ignoring the transform stack.
+    pub fn screen_to_world(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.buf.size();
+        match self.camera {
+            Some(camera) => camera.screen_to_world(Vec2::new(x, y), (width, height)).into(),
+            None => (
+                x / (width - 1) as f32 * 2. - 1.,
+                1. - y / (height - 1) as f32 * 2.,
+            ),
+        }
+    }
+
+    /// Converts world coordinates (or normalized coordinates without a
+    /// camera) into a pixel position, ignoring the transform stack.
+    pub fn world_to_screen(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.buf.size();
+        match self.camera {
+            Some(camera) => camera.world_to_screen(Vec2::new(x, y), (width, height)).into(),
+            None => (
+                (x + 1.) / 2. * (width - 1) as f32,
+                (height - 1) as f32 - (y + 1.) / 2. * (height - 1) as f32,
+            ),
         }
     }
 
@@ -19,6 +103,19 @@ impl Canvas {
         &self.buf
     }
 
+    /// The backing buffer, e.g. to post-process a finished frame.
+    pub fn as_buffer(&self) -> &Buffer {
+        &self.buf
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.buf.save_png(path)
+    }
+
+    pub fn save_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.buf.save_ppm(path)
+    }
+
     pub fn clear(&mut self) {
         self.buf.clear();
     }
@@ -27,17 +124,99 @@ impl Canvas {
         self.buf.fill();
     }
 
-    pub fn set_color(&mut self, color: u32) {
+    pub fn set_color(&mut self, color: impl Into<Color>) {
         self.buf.set_color(color);
     }
 
-    fn point_to_pixel(&self, (x, y): (f32, f32)) -> (usize, usize) {
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.buf.set_alpha(alpha);
+    }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.buf.set_blend_mode(mode);
+    }
+
+    pub fn set_text_style(&mut self, style: TextStyle) {
+        self.text_style = style;
+    }
+
+    pub fn stroke_style(&self) -> &StrokeStyle {
+        &self.stroke_style
+    }
+
+    /// Sets how `line`, `tri`, `sqr` and `polyline` draw their outlines.
+    pub fn set_stroke_style(&mut self, style: StrokeStyle) {
+        self.stroke_style = style;
+    }
+
+    /// Restricts drawing to the rectangle spanned by two corners until the
+    /// matching `pop_clip`. Nested clips intersect with the enclosing one.
+    pub fn push_clip(&mut self, p1: (f32, f32), p2: (f32, f32)) {
+        let rect = Rect::from_corners(self.point_to_pixel(p1), self.point_to_pixel(p2));
+        self.buf.push_clip(rect);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.buf.pop_clip();
+    }
+
+    /// Saves the current transform so it can be restored with `pop_transform`.
+    pub fn push_transform(&mut self) {
+        self.transform_stack.push(self.transform);
+    }
+
+    pub fn pop_transform(&mut self) {
+        if let Some(transform) = self.transform_stack.pop() {
+            self.transform = transform;
+        }
+    }
+
+    pub fn transform(&self) -> Mat3x3<f32> {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Mat3x3<f32>) {
+        self.transform = transform;
+    }
+
+    pub fn reset_transform(&mut self) {
+        self.transform = Mat3x3::identity();
+    }
+
+    /// Composes `transform` with the current one, so it applies to points
+    /// before any previously applied transforms.
+    pub fn apply_transform(&mut self, transform: Mat3x3<f32>) {
+        self.transform = self.transform * transform;
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.apply_transform(Mat3x3::translation(x, y));
+    }
+
+    /// Rotates counter-clockwise by `angle` radians around the local origin.
+    pub fn rotate(&mut self, angle: f32) {
+        self.apply_transform(Mat3x3::rotation(angle));
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.apply_transform(Mat3x3::scale(x, y));
+    }
+
+    /// Maps a point in local coordinates through the current transform to a
+    /// pixel. Sprites and text only transform their anchor points, so they
+    /// stay axis-aligned under rotation.
+    fn point_to_pixel(&self, p: (f32, f32)) -> (i32, i32) {
+        let (x, y) = self.transform.transform_point(Vec2::from(p)).into();
         let (width, height) = self.buf.size();
+        if let Some(camera) = self.camera {
+            let p = camera.world_to_screen(Vec2::new(x, y), (width, height));
+            return (p.x.floor() as i32, p.y.floor() as i32);
+        }
         let x = (x + 1.) / 2.;
         let y = (y + 1.) / 2.;
         (
-            (x * (width - 1) as f32) as usize,
-            (height - 1) - (y * (height - 1) as f32) as usize,
+            (x * (width - 1) as f32).floor() as i32,
+            (height - 1) as i32 - (y * (height - 1) as f32).floor() as i32,
         )
     }
 
@@ -45,25 +224,616 @@ impl Canvas {
         self.buf.pixel(self.point_to_pixel(index));
     }
 
+    /// Like `point_to_pixel`, but keeps the sub-pixel position for strokes.
+    /// Without a camera, points land on pixel centers.
+    fn point_to_screen(&self, p: (f32, f32)) -> (f32, f32) {
+        let p = self.transform.transform_point(Vec2::from(p)).into();
+        let (x, y) = self.world_to_screen(p);
+        match self.camera {
+            Some(_) => (x, y),
+            None => (x + 0.5, y + 0.5),
+        }
+    }
+
+    /// Outlines the points with the current stroke style. Plain one pixel
+    /// strokes keep using Bresenham lines between the mapped pixels.
+    fn stroke(&mut self, points: &[(f32, f32)], closed: bool) {
+        if self.stroke_style.is_plain() {
+            let pixels: Vec<_> = points.iter().map(|&p| self.point_to_pixel(p)).collect();
+            let count = if closed { pixels.len() } else { pixels.len().saturating_sub(1) };
+            for i in 0..count {
+                self.buf.line(pixels[i], pixels[(i + 1) % pixels.len()]);
+            }
+            return;
+        }
+        let points: Vec<_> = points.iter().map(|&p| self.point_to_screen(p)).collect();
+        self.buf.stroke(&points, closed, &self.stroke_style)
+    }
+
     pub fn line(&mut self, p1: (f32, f32), p2: (f32, f32)) {
-        self.buf
-            .line(self.point_to_pixel(p1), self.point_to_pixel(p2))
+        self.stroke(&[p1, p2], false)
+    }
+
+    pub fn polyline(&mut self, points: &[(f32, f32)]) {
+        self.stroke(points, false)
     }
 
     pub fn tri(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
-        self.buf.tri(
+        self.stroke(&[p1, p2, p3], true)
+    }
+
+    pub fn sqr(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), p4: (f32, f32)) {
+        self.stroke(&[p1, p2, p3, p4], true)
+    }
+
+    pub fn fill_tri(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        self.buf.fill_tri(
             self.point_to_pixel(p1),
             self.point_to_pixel(p2),
             self.point_to_pixel(p3),
         )
     }
 
-    pub fn sqr(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), p4: (f32, f32)) {
-        self.buf.sqr(
+    pub fn fill_quad(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), p4: (f32, f32)) {
+        self.buf.fill_quad(
             self.point_to_pixel(p1),
             self.point_to_pixel(p2),
             self.point_to_pixel(p3),
             self.point_to_pixel(p4),
         )
     }
-}
\ No newline at end of file
+
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        let points: Vec<_> = points.iter().map(|&p| self.point_to_pixel(p)).collect();
+        self.buf.fill_polygon(&points)
+    }
+
+    /// Whether the transform keeps axis-aligned shapes axis-aligned, so
+    /// ellipses can be drawn with the midpoint algorithm.
+    fn is_axis_aligned(&self) -> bool {
+        self.transform.m[0][1] == 0.0 && self.transform.m[1][0] == 0.0
+    }
+
+    /// Length in pixels of the longer of the two radii after mapping, used
+    /// to pick how finely to flatten curves.
+    fn pixel_radius(&self, center: (f32, f32), rx: f32, ry: f32) -> f32 {
+        let c = Vec2::from(self.point_to_screen(center));
+        let x = Vec2::from(self.point_to_screen((center.0 + rx, center.1)));
+        let y = Vec2::from(self.point_to_screen((center.0, center.1 + ry)));
+        c.distance(x).max(c.distance(y))
+    }
+
+    fn ellipse_points(&self, center: (f32, f32), rx: f32, ry: f32, start: f32, end: f32) -> Vec<(f32, f32)> {
+        let segments = shapes::arc_segments(self.pixel_radius(center, rx, ry), end - start);
+        shapes::arc_points(center, (rx, ry), start, end, segments)
+    }
+
+    /// The pixel center and radii of an ellipse, if it is still axis-aligned
+    /// on screen and small enough for the midpoint algorithm.
+    fn ellipse_to_pixels(&self, center: (f32, f32), rx: f32, ry: f32) -> Option<((i32, i32), i32, i32)> {
+        if !self.is_axis_aligned() {
+            return None;
+        }
+        let c = self.point_to_screen(center);
+        let x = self.point_to_screen((center.0 + rx, center.1));
+        let y = self.point_to_screen((center.0, center.1 + ry));
+        let rx = (x.0 - c.0).abs().round();
+        let ry = (y.1 - c.1).abs().round();
+        // Also false for NaN radii
+        let limit = self.buf.max_midpoint_radius() as f32;
+        if !(rx <= limit && ry <= limit) {
+            return None;
+        }
+        Some((self.point_to_pixel(center), rx as i32, ry as i32))
+    }
+
+    /// The radius is in local units, so circles stretch into ellipses when
+    /// the mapping to pixels is not uniform.
+    pub fn circle(&mut self, center: (f32, f32), radius: f32) {
+        self.ellipse(center, radius, radius)
+    }
+
+    pub fn fill_circle(&mut self, center: (f32, f32), radius: f32) {
+        self.fill_ellipse(center, radius, radius)
+    }
+
+    /// Axis-aligned ellipse outline. Uses the midpoint algorithm for plain
+    /// strokes, otherwise strokes a flattened outline.
+    pub fn ellipse(&mut self, center: (f32, f32), rx: f32, ry: f32) {
+        match self.ellipse_to_pixels(center, rx, ry) {
+            Some((c, rx, ry)) if self.stroke_style.is_plain() => self.buf.ellipse(c, rx, ry),
+            _ => {
+                let mut points = self.ellipse_points(center, rx, ry, 0.0, TAU);
+                points.pop();
+                self.stroke(&points, true)
+            }
+        }
+    }
+
+    pub fn fill_ellipse(&mut self, center: (f32, f32), rx: f32, ry: f32) {
+        match self.ellipse_to_pixels(center, rx, ry) {
+            Some((c, rx, ry)) => self.buf.fill_ellipse(c, rx, ry),
+            None => {
+                let points = self.ellipse_points(center, rx, ry, 0.0, TAU);
+                self.fill_polygon(&points)
+            }
+        }
+    }
+
+    /// Circular arc from `start` to `end` radians, counter-clockwise from the
+    /// positive x axis.
+    pub fn arc(&mut self, center: (f32, f32), radius: f32, start: f32, end: f32) {
+        let points = self.ellipse_points(center, radius, radius, start, end);
+        self.stroke(&points, false)
+    }
+
+    fn rounded_rect_points(&self, p1: (f32, f32), p2: (f32, f32), radius: f32) -> Vec<(f32, f32)> {
+        let segments = shapes::arc_segments(self.pixel_radius(p1, radius, radius), TAU / 4.0);
+        shapes::rounded_rect_points(p1, p2, radius, segments)
+    }
+
+    /// Rectangle between two opposite corners with rounded corners, e.g. for
+    /// UI panels.
+    pub fn rounded_rect(&mut self, p1: (f32, f32), p2: (f32, f32), radius: f32) {
+        let points = self.rounded_rect_points(p1, p2, radius);
+        self.stroke(&points, true)
+    }
+
+    pub fn fill_rounded_rect(&mut self, p1: (f32, f32), p2: (f32, f32), radius: f32) {
+        let points = self.rounded_rect_points(p1, p2, radius);
+        let points: Vec<_> = points.iter().map(|&p| self.point_to_screen(p)).collect();
+        self.buf.fill_polygon_f32(&points)
+    }
+
+    /// Quadratic Bézier curve from `p0` to `p2`, pulled towards `p1`. Curves
+    /// are flattened after mapping, so they stay smooth at any zoom.
+    pub fn quadratic(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) {
+        let [p0, p1, p2] = [p0, p1, p2].map(|p| self.point_to_screen(p));
+        let points = shapes::flatten_quadratic(p0, p1, p2, shapes::TOLERANCE);
+        self.buf.stroke(&points, false, &self.stroke_style)
+    }
+
+    /// Cubic Bézier curve from `p0` to `p3` with control points `p1` and `p2`.
+    pub fn cubic(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        let [p0, p1, p2, p3] = [p0, p1, p2, p3].map(|p| self.point_to_screen(p));
+        let points = shapes::flatten_cubic(p0, p1, p2, p3, shapes::TOLERANCE);
+        self.buf.stroke(&points, false, &self.stroke_style)
+    }
+
+    /// Draws a sprite stretched between two opposite corners.
+    pub fn draw_sprite(&mut self, sprite: &Sprite, p1: (f32, f32), p2: (f32, f32), blit: &Blit) {
+        let dest = Rect::from_corners(self.point_to_pixel(p1), self.point_to_pixel(p2));
+        self.buf.blit(sprite, dest, blit)
+    }
+
+    /// Draws the current frame of `animation`, taken from `sheet`, stretched
+    /// between two opposite corners. `blit.source` is replaced by the frame.
+    pub fn draw_animation(&mut self, sheet: &Sprite, animation: &Animation, p1: (f32, f32), p2: (f32, f32), blit: &Blit) {
+        if let Some(source) = animation.frame() {
+            let blit = Blit {
+                source: Some(source),
+                ..*blit
+            };
+            self.draw_sprite(sheet, p1, p2, &blit)
+        }
+    }
+
+    /// Bounding box, in local coordinates, of what the current clip
+    /// rectangle shows through the transform.
+    fn visible_bounds(&self) -> ((f32, f32), (f32, f32)) {
+        let rect = self.buf.clip_rect();
+        let Some(inverse) = self.transform.inverse() else {
+            return ((0.0, 0.0), (0.0, 0.0));
+        };
+        let corners = [
+            (rect.x, rect.y),
+            (rect.right(), rect.y),
+            (rect.x, rect.bottom()),
+            (rect.right(), rect.bottom()),
+        ]
+        .map(|(x, y)| {
+            let world = self.screen_to_world((x as f32, y as f32));
+            inverse.transform_point(Vec2::from(world))
+        });
+        let min = corners.iter().fold(Vec2::splat(f32::INFINITY), |a, &b| a.min(b));
+        let max = corners.iter().fold(Vec2::splat(f32::NEG_INFINITY), |a, &b| a.max(b));
+        (min.into(), max.into())
+    }
+
+    /// Draws every visible tile of the map; tiles outside the clip rectangle
+    /// are skipped without being looked at.
+    pub fn draw_tilemap(&mut self, map: &Tilemap) {
+        let (min, max) = self.visible_bounds();
+        let (columns, rows) = map.visible_range(min, max);
+        let previous = self.buf.color();
+        for row in rows {
+            for column in columns.clone() {
+                let (p1, p2) = map.tile_bounds(column, row);
+                match map.look(map.get(column, row)) {
+                    TileLook::Empty => {}
+                    TileLook::Color(color) => {
+                        self.set_color(color);
+                        self.fill_quad(p1, (p2.0, p1.1), p2, (p1.0, p2.1));
+                    }
+                    TileLook::Sprite(index) => {
+                        self.draw_sprite(map.sprite(index), (p1.0, p2.1), (p2.0, p1.1), &Blit::default())
+                    }
+                }
+            }
+        }
+        self.buf.set_color(previous);
+    }
+
+    /// Draws text anchored at `pos` using the current text style.
+    pub fn text(&mut self, pos: (f32, f32), text: &str) {
+        self.buf.text(self.point_to_pixel(pos), text, &self.text_style)
+    }
+
+    /// Renders a mesh as a wireframe into the current viewport.
+    pub fn wireframe(&mut self, pipeline: &Pipeline, mesh: &Mesh) {
+        pipeline.draw_wireframe(&mut self.buf, mesh)
+    }
+
+    pub fn enable_depth(&mut self) {
+        self.buf.enable_depth();
+    }
+
+    /// Renders a mesh as shaded, optionally textured triangles into the
+    /// current viewport.
+    pub fn solid(&mut self, pipeline: &Pipeline, mesh: &Mesh, texture: Option<&Sprite>) {
+        pipeline.draw_solid(&mut self.buf, mesh, texture)
+    }
+
+    /// Draws the commands recorded on a `TileRenderer` in parallel.
+    pub fn render_tiles(&mut self, tiles: &TileRenderer) {
+        tiles.render(&mut self.buf)
+    }
+
+    /// Pixels a recorded command can touch with the current camera and
+    /// size, within the viewport.
+    fn command_bounds(&self, command: &Command) -> Rect {
+        let to_screen = |p: (f32, f32)| {
+            let p = command.transform.transform_point(Vec2::from(p)).into();
+            Vec2::from(self.world_to_screen(p))
+        };
+        let around = |points: &[(f32, f32)], margin: f32| {
+            let screen = points.iter().map(|&p| to_screen(p));
+            let min = screen.clone().fold(Vec2::splat(f32::INFINITY), |a, b| a.min(b));
+            let max = screen.fold(Vec2::splat(f32::NEG_INFINITY), |a, b| a.max(b));
+            let (x, y) = ((min.x - margin).floor() as i32, (min.y - margin).floor() as i32);
+            let (right, bottom) = ((max.x + margin).floor() as i32 + 1, (max.y + margin).floor() as i32 + 1);
+            Rect::new(x, y, right - x, bottom - y)
+        };
+        let bounds = match &command.shape {
+            Shape::Fill => self.buf.viewport(),
+            Shape::Polygon(points) => around(points, 1.0),
+            // Miter joins reach furthest, square caps at most width / sqrt(2)
+            Shape::Stroke { points, style, .. } => around(points, style.width * style.miter_limit.max(1.5) / 2.0 + 1.0),
+            Shape::Text { pos, text, style } => {
+                let anchor = to_screen(*pos);
+                let (x, y) = (anchor.x.floor() as i32, anchor.y.floor() as i32);
+                let scale = style.scale.max(1) as i32;
+                let width = text.split('\n').map(font::line_width).max().unwrap_or(0) as i32 * scale;
+                let height = text.split('\n').count() as i32 * font::LINE_HEIGHT as i32 * scale;
+                let left = match style.align {
+                    Align::Left => x,
+                    Align::Center => x - width / 2,
+                    Align::Right => x - width,
+                };
+                Rect::new(left - 2, y - 2, width + 4, height + 4)
+            }
+            Shape::Sprite { p1, p2, .. } => around(&[*p1, *p2], 1.0),
+        };
+        bounds.intersect(&self.buf.viewport())
+    }
+
+    /// Draws one recorded command with the state it was recorded with.
+    fn draw_command(&mut self, command: &Command) {
+        self.buf.set_color(command.color);
+        self.buf.set_blend_mode(command.blend_mode);
+        self.transform = command.transform;
+        match &command.shape {
+            Shape::Fill => self.buf.fill(),
+            Shape::Polygon(points) => self.fill_polygon(points),
+            Shape::Stroke { points, closed, style } => {
+                let previous = std::mem::replace(&mut self.stroke_style, style.clone());
+                self.stroke(points, *closed);
+                self.stroke_style = previous;
+            }
+            Shape::Text { pos, text, style } => self.buf.text(self.point_to_pixel(*pos), text, style),
+            Shape::Sprite { sprite, p1, p2, blit } => self.draw_sprite(&sprite.0, *p1, *p2, blit),
+        }
+    }
+
+    /// Clears the canvas and draws every command of `list`. The canvas's own
+    /// color, blend mode and transform are left as they were.
+    pub fn replay(&mut self, list: &DisplayList) {
+        self.clear();
+        let (color, mode, transform) = (self.buf.color(), self.buf.blend_mode(), self.transform);
+        for command in list.commands() {
+            self.draw_command(command);
+        }
+        self.buf.set_color(color);
+        self.buf.set_blend_mode(mode);
+        self.transform = transform;
+    }
+
+    /// Screen rectangles where a canvas showing `previous` has to change to
+    /// show `next`, assuming the camera and size stayed the same. The
+    /// longest run of commands found in both lists in the same order is
+    /// skipped; the bounds of all others, old and new, are merged into as
+    /// few overlapping rectangles as possible. A command that only moved in
+    /// the drawing order counts as changed, since that changes what ends up
+    /// on top.
+    pub fn diff(&self, previous: &DisplayList, next: &DisplayList) -> Vec<Rect> {
+        let (old, new) = (previous.commands(), next.commands());
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let (old, new) = (&old[prefix..], &new[prefix..]);
+        let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+        let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+        let (old_kept, new_kept) = common_subsequence(old, new);
+        let mut dirty = Vec::new();
+        for (command, _) in old.iter().zip(old_kept).chain(new.iter().zip(new_kept)).filter(|(_, kept)| !kept) {
+            dirty.push(self.command_bounds(command));
+        }
+
+        let mut merged: Vec<Rect> = Vec::new();
+        for mut rect in dirty.into_iter().filter(|rect| !rect.is_empty()) {
+            // A grown rectangle can reach ones that were merged before
+            while let Some(i) = merged.iter().position(|m| !m.intersect(&rect).is_empty()) {
+                rect = rect.union(&merged.swap_remove(i));
+            }
+            merged.push(rect);
+        }
+        merged
+    }
+
+    /// Clears each `dirty` rectangle and draws the commands of `list` that
+    /// touch it, clipped to it. Given the rectangles from `diff`, the result
+    /// is the same as replaying `list`, at a fraction of the work when
+    /// little changed.
+    pub fn redraw(&mut self, list: &DisplayList, dirty: &[Rect]) {
+        let (color, mode, transform) = (self.buf.color(), self.buf.blend_mode(), self.transform);
+        for &rect in dirty {
+            self.buf.push_clip(rect);
+            self.buf.set_color(Color::BLACK);
+            self.buf.set_blend_mode(BlendMode::Normal);
+            self.buf.fill();
+            for command in list.commands() {
+                if !self.command_bounds(command).intersect(&rect).is_empty() {
+                    self.draw_command(command);
+                }
+            }
+            self.buf.pop_clip();
+        }
+        self.buf.set_color(color);
+        self.buf.set_blend_mode(mode);
+        self.transform = transform;
+    }
+}
+
+/// Marks the commands of a longest common subsequence of `old` and `new`,
+/// the ones that can be kept without changing the drawing order. Past a few
+/// million pairs nothing is kept, which redraws more but stays correct.
+fn common_subsequence(old: &[Command], new: &[Command]) -> (Vec<bool>, Vec<bool>) {
+    let (mut old_kept, mut new_kept) = (vec![false; old.len()], vec![false; new.len()]);
+    if old.len().saturating_mul(new.len()) > 1 << 22 {
+        return (old_kept, new_kept);
+    }
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            (old_kept[i], new_kept[j]) = (true, true);
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (old_kept, new_kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(list: &mut DisplayList, color: Color, (x, y): (f32, f32), size: f32) {
+        list.set_color(color);
+        list.fill_quad((x, y), (x + size, y), (x + size, y + size), (x, y + size));
+    }
+
+    /// Shows `previous`, then moves to `next` with `diff` and `redraw` and
+    /// checks every pixel against replaying `next` from scratch.
+    fn assert_redraw_matches_replay(previous: &DisplayList, next: &DisplayList) -> Vec<Rect> {
+        let mut canvas = Canvas::new(64, 48);
+        canvas.replay(previous);
+        let dirty = canvas.diff(previous, next);
+        canvas.redraw(next, &dirty);
+        let mut expected = Canvas::new(64, 48);
+        expected.replay(next);
+        let (pixels, replayed): (&[u32], &[u32]) = (canvas.as_buffer(), expected.as_buffer());
+        assert!(pixels == replayed, "redraw differs from replay");
+        dirty
+    }
+
+    fn scene(order: &[(Color, (f32, f32))]) -> DisplayList {
+        let mut list = DisplayList::new();
+        list.set_color(Color::GRAY);
+        list.fill();
+        for &(color, corner) in order {
+            square(&mut list, color, corner, 0.6);
+        }
+        list
+    }
+
+    #[test]
+    fn identical_lists_have_nothing_to_redraw() {
+        let list = scene(&[(Color::RED, (-0.5, -0.5)), (Color::BLUE, (0.0, 0.0))]);
+        assert!(assert_redraw_matches_replay(&list, &list.clone()).is_empty());
+    }
+
+    #[test]
+    fn moved_command_dirties_old_and_new_place() {
+        let previous = scene(&[(Color::RED, (-0.9, -0.9)), (Color::BLUE, (0.2, 0.2))]);
+        let next = scene(&[(Color::RED, (-0.9, -0.9)), (Color::BLUE, (0.3, -0.8))]);
+        let dirty = assert_redraw_matches_replay(&previous, &next);
+        assert!(!dirty.is_empty());
+    }
+
+    #[test]
+    fn reordered_overlapping_commands_are_redrawn() {
+        let a = (Color::RED, (-0.5, -0.5));
+        let b = (Color::BLUE, (-0.2, -0.2));
+        let c = (Color::GREEN, (0.3, 0.3));
+        let previous = scene(&[a, b, c]);
+        let next = scene(&[b, a, c]);
+        assert!(!assert_redraw_matches_replay(&previous, &next).is_empty());
+        assert_redraw_matches_replay(&scene(&[a, c, b]), &scene(&[b, c, a]));
+    }
+
+    #[test]
+    fn added_and_removed_commands_are_redrawn() {
+        let a = (Color::RED, (-0.9, -0.9));
+        let b = (Color::BLUE, (-0.1, -0.1));
+        let c = (Color::YELLOW, (0.3, -0.7));
+        assert_redraw_matches_replay(&scene(&[a, b]), &scene(&[a, c, b]));
+        assert_redraw_matches_replay(&scene(&[a, c, b]), &scene(&[b]));
+    }
+
+    #[test]
+    fn random_scenes_redraw_like_replay() {
+        use crate::stroke::{LineCap, LineJoin};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        /// One random command: a translucent polygon, anti-aliased line,
+        /// thick stroke or text.
+        fn command(rng: &mut StdRng) -> DisplayList {
+            let mut list = DisplayList::new();
+            list.set_color(Color::random().with_alpha(rng.gen_range(64..=255)));
+            let mut point = || (rng.gen_range(-1.2..1.2), rng.gen_range(-1.2..1.2));
+            let points: Vec<_> = (0..4).map(|_| point()).collect();
+            match rng.gen_range(0..4) {
+                0 => list.fill_polygon(&points[..rng.gen_range(3..=4)]),
+                1 => {
+                    list.set_stroke_style(StrokeStyle {
+                        antialias: true,
+                        ..Default::default()
+                    });
+                    list.line(points[0], points[1]);
+                }
+                2 => {
+                    list.set_stroke_style(StrokeStyle {
+                        width: rng.gen_range(1.5..6.0),
+                        antialias: rng.gen(),
+                        join: [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel][rng.gen_range(0..3)],
+                        cap: [LineCap::Butt, LineCap::Round, LineCap::Square][rng.gen_range(0..3)],
+                        ..Default::default()
+                    });
+                    list.polyline(&points[..rng.gen_range(2..=4)]);
+                }
+                _ => list.text(points[0], "Ty 42"),
+            }
+            list
+        }
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..300 {
+            let mut commands: Vec<_> = (0..rng.gen_range(1..8)).map(|_| command(&mut rng)).collect();
+            let mut previous = DisplayList::new();
+            commands.iter().for_each(|c| previous.append(c));
+            // Move, replace or add a single command
+            let index = rng.gen_range(0..commands.len());
+            match rng.gen_range(0..3) {
+                0 => {
+                    let moved = commands.remove(index);
+                    commands.push(moved);
+                }
+                1 => commands[index] = command(&mut rng),
+                _ => commands.insert(index, command(&mut rng)),
+            }
+            let mut next = DisplayList::new();
+            commands.iter().for_each(|c| next.append(c));
+            assert_redraw_matches_replay(&previous, &next);
+        }
+    }
+
+    #[test]
+    fn huge_zoomed_circles_fall_back_to_polygons() {
+        let mut canvas = Canvas::new(21, 21);
+        canvas.scale(1000.0, 1000.0);
+        canvas.circle((0.0, 0.0), 1000.0);
+        assert!(canvas.buffer().iter().all(|&p| p == 0));
+        canvas.fill_circle((0.0, 0.0), 1000.0);
+        assert!(canvas.buffer().iter().all(|&p| p == 0xFFFFFF));
+    }
+
+    /// The one lit pixel of a 21x21 canvas after `draw`. Normalized
+    /// coordinates step 0.1 per pixel there, so odd multiples of 0.05 fall
+    /// inside a pixel instead of on its edge.
+    fn lit_pixel(draw: impl FnOnce(&mut Canvas)) -> (usize, usize) {
+        let mut canvas = Canvas::new(21, 21);
+        draw(&mut canvas);
+        let pixels = canvas.buffer();
+        let lit: Vec<_> = (0..pixels.len()).filter(|&i| pixels[i] != 0).collect();
+        assert_eq!(lit.len(), 1, "expected a single pixel");
+        (lit[0] % 21, lit[0] / 21)
+    }
+
+    #[test]
+    fn transforms_move_drawing() {
+        assert_eq!(lit_pixel(|c| c.pixel((0.05, 0.05))), (10, 10));
+        assert_eq!(lit_pixel(|c| {
+            c.translate(0.5, 0.0);
+            c.pixel((0.05, 0.05));
+        }), (15, 10));
+        assert_eq!(lit_pixel(|c| {
+            c.scale(2.0, 2.0);
+            c.pixel((0.275, 0.025));
+        }), (15, 10));
+        assert_eq!(lit_pixel(|c| {
+            c.rotate(std::f32::consts::FRAC_PI_2);
+            c.pixel((0.55, 0.05));
+        }), (9, 5));
+    }
+
+    #[test]
+    fn later_transforms_apply_first() {
+        let mut canvas = Canvas::new(21, 21);
+        canvas.translate(0.5, 0.0);
+        canvas.scale(2.0, 1.0);
+        let p = canvas.transform().transform_point(Vec2::new(0.1, 0.3));
+        assert!((p.x - 0.7).abs() < 1e-6 && (p.y - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pop_restores_pushed_transform() {
+        assert_eq!(lit_pixel(|c| {
+            c.translate(-0.5, 0.0);
+            c.push_transform();
+            c.rotate(1.0);
+            c.scale(3.0, 3.0);
+            c.pop_transform();
+            c.pop_transform();
+            c.pixel((0.05, 0.05));
+        }), (5, 10));
+        let mut canvas = Canvas::new(21, 21);
+        canvas.push_transform();
+        canvas.translate(0.25, 0.5);
+        canvas.reset_transform();
+        assert_eq!(canvas.transform(), Mat3x3::identity());
+    }
+}
@@ -0,0 +1,332 @@
+#![allow(unused)]
+
+/// A pixel rectangle. Its edges saturate at the ends of `i32` instead of
+/// overflowing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Largest corner coordinate `Rect::from_corners` keeps.
+pub const CORNER_LIMIT: i32 = i32::MAX / 2;
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width: width.max(0),
+            height: height.max(0),
+        }
+    }
+
+    /// Builds the rectangle spanning two opposite corners, both inclusive.
+    /// Corners are first clamped to `±CORNER_LIMIT`, far outside any buffer,
+    /// so the size always fits in an `i32`.
+    pub fn from_corners((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> Self {
+        let clamp = |v: i32| v.clamp(-CORNER_LIMIT, CORNER_LIMIT);
+        let (x1, y1, x2, y2) = (clamp(x1), clamp(y1), clamp(x2), clamp(y2));
+        let (left, right) = (x1.min(x2), x1.max(x2));
+        let (top, bottom) = (y1.min(y2), y1.max(y2));
+        Self::new(left, top, right - left + 1, bottom - top + 1)
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add(self.width)
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().min(other.right()).saturating_sub(x),
+            self.bottom().min(other.bottom()).saturating_sub(y),
+        )
+    }
+
+    /// The smallest rectangle containing both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().max(other.right()).saturating_sub(x),
+            self.bottom().max(other.bottom()).saturating_sub(y),
+        )
+    }
+}
+
+const INSIDE: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTTOM: u8 = 4;
+const TOP: u8 = 8;
+
+/// Bounds as `(xmin, ymin, xmax, ymax)`, all inclusive.
+type Bounds = (f32, f32, f32, f32);
+
+fn outcode((xmin, ymin, xmax, ymax): Bounds, (x, y): (f32, f32)) -> u8 {
+    let mut code = INSIDE;
+    if x < xmin {
+        code |= LEFT;
+    } else if x > xmax {
+        code |= RIGHT;
+    }
+    if y < ymin {
+        code |= TOP;
+    } else if y > ymax {
+        code |= BOTTOM;
+    }
+    code
+}
+
+/// The pixel `step` steps along the line `Buffer::line` draws from `p1` to
+/// `p2`: every step moves one pixel along the longer axis, and the other
+/// coordinate is rounded to the nearest pixel, halves away from `p1`.
+pub fn line_pixel(p1: (i32, i32), p2: (i32, i32), step: i64) -> (i64, i64) {
+    let (dx, dy) = (p2.0 as i64 - p1.0 as i64, p2.1 as i64 - p1.1 as i64);
+    let major = dx.abs().max(dy.abs()) as i128;
+    if major == 0 {
+        return (p1.0 as i64, p1.1 as i64);
+    }
+    let along = |delta: i64| ((2 * delta.abs() as i128 * step as i128 + major) / (2 * major)) as i64 * delta.signum();
+    (p1.0 as i64 + along(dx), p1.1 as i64 + along(dy))
+}
+
+/// The first and last steps of `line_pixel`'s line from `p1` to `p2` whose
+/// pixels are in `rect`. Every step between them is inside too. Returns
+/// `None` when no pixel of the line is visible.
+pub fn clip_line_steps(rect: &Rect, p1: (i32, i32), p2: (i32, i32)) -> Option<(i64, i64)> {
+    if rect.is_empty() {
+        return None;
+    }
+    let (dx, dy) = (p2.0 as i64 - p1.0 as i64, p2.1 as i64 - p1.1 as i64);
+    let steps = dx.abs().max(dy.abs());
+    // Pixels are within half a pixel of the exact line, so only the steps
+    // where it passes within a pixel of the rect can be inside
+    let (mut low, mut high) = (0.0, steps as f64);
+    for (start, delta, min, end) in [(p1.0, dx, rect.x, rect.right()), (p1.1, dy, rect.y, rect.bottom())] {
+        let (min, max) = (min as f64 - 1.0 - start as f64, end as f64 - start as f64);
+        if delta == 0 {
+            if !(min <= 0.0 && 0.0 <= max) {
+                return None;
+            }
+            continue;
+        }
+        let scale = steps as f64 / delta as f64;
+        let (a, b) = (min * scale, max * scale);
+        low = f64::max(low, a.min(b));
+        high = f64::min(high, a.max(b));
+    }
+    if low > high {
+        return None;
+    }
+    let inside = |step| {
+        let (x, y) = line_pixel(p1, p2, step);
+        (rect.x as i64..rect.right() as i64).contains(&x) && (rect.y as i64..rect.bottom() as i64).contains(&y)
+    };
+    let (mut first, mut last) = ((low.floor() as i64).max(0), (high.ceil() as i64).min(steps));
+    while first <= last && !inside(first) {
+        first += 1;
+    }
+    while last >= first && !inside(last) {
+        last -= 1;
+    }
+    (first <= last).then_some((first, last))
+}
+
+/// The first and last pixels of the line from `p1` to `p2` that fall in
+/// `rect`, as `Buffer::line` draws it. Returns `None` when no part of the
+/// line is visible.
+pub fn clip_line(rect: &Rect, p1: (i32, i32), p2: (i32, i32)) -> Option<((i32, i32), (i32, i32))> {
+    let (first, last) = clip_line_steps(rect, p1, p2)?;
+    let pixel = |step| {
+        let (x, y) = line_pixel(p1, p2, step);
+        (x as i32, y as i32)
+    };
+    Some((pixel(first), pixel(last)))
+}
+
+/// Clips a segment in continuous coordinates against the area covered by
+/// `rect`, treating pixel edges (not centers) as the boundary.
+pub fn clip_line_f32(rect: &Rect, p1: (f32, f32), p2: (f32, f32)) -> Option<((f32, f32), (f32, f32))> {
+    if rect.is_empty() {
+        return None;
+    }
+    let bounds = (rect.x as f32, rect.y as f32, rect.right() as f32, rect.bottom() as f32);
+    clip_segment(bounds, p1, p2)
+}
+
+fn clip_segment(bounds: Bounds, mut a: (f32, f32), mut b: (f32, f32)) -> Option<((f32, f32), (f32, f32))> {
+    let (xmin, ymin, xmax, ymax) = bounds;
+    let mut code_a = outcode(bounds, a);
+    let mut code_b = outcode(bounds, b);
+    loop {
+        if code_a | code_b == INSIDE {
+            return Some((a, b));
+        }
+        if code_a & code_b != INSIDE {
+            return None;
+        }
+        let code = if code_a != INSIDE { code_a } else { code_b };
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let point = if code & TOP != 0 {
+            (a.0 + dx * (ymin - a.1) / dy, ymin)
+        } else if code & BOTTOM != 0 {
+            (a.0 + dx * (ymax - a.1) / dy, ymax)
+        } else if code & RIGHT != 0 {
+            (xmax, a.1 + dy * (xmax - a.0) / dx)
+        } else {
+            (xmin, a.1 + dy * (xmin - a.0) / dx)
+        };
+        if code == code_a {
+            a = point;
+            code_a = outcode(bounds, a);
+        } else {
+            b = point;
+            code_b = outcode(bounds, b);
+        }
+    }
+}
+
+/// Sutherland–Hodgman clipping of a polygon against the area covered by
+/// `rect`, treating pixel edges (not centers) as the boundary.
+pub fn clip_polygon(rect: &Rect, points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let (xmin, xmax) = (rect.x as f32, rect.right() as f32);
+    let (ymin, ymax) = (rect.y as f32, rect.bottom() as f32);
+    let mut output = points.to_vec();
+    output = clip_edge(&output, |p| p.0 >= xmin, |a, b| {
+        (xmin, a.1 + (b.1 - a.1) * (xmin - a.0) / (b.0 - a.0))
+    });
+    output = clip_edge(&output, |p| p.0 <= xmax, |a, b| {
+        (xmax, a.1 + (b.1 - a.1) * (xmax - a.0) / (b.0 - a.0))
+    });
+    output = clip_edge(&output, |p| p.1 >= ymin, |a, b| {
+        (a.0 + (b.0 - a.0) * (ymin - a.1) / (b.1 - a.1), ymin)
+    });
+    clip_edge(&output, |p| p.1 <= ymax, |a, b| {
+        (a.0 + (b.0 - a.0) * (ymax - a.1) / (b.1 - a.1), ymax)
+    })
+}
+
+fn clip_edge(
+    points: &[(f32, f32)],
+    inside: impl Fn((f32, f32)) -> bool,
+    intersect: impl Fn((f32, f32), (f32, f32)) -> (f32, f32),
+) -> Vec<(f32, f32)> {
+    let mut output = Vec::with_capacity(points.len() + 2);
+    for i in 0..points.len() {
+        let current = points[i];
+        let previous = points[(i + points.len() - 1) % points.len()];
+        match (inside(previous), inside(current)) {
+            (true, true) => output.push(current),
+            (true, false) => output.push(intersect(previous, current)),
+            (false, true) => {
+                output.push(intersect(previous, current));
+                output.push(current);
+            }
+            (false, false) => {}
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 10,
+        height: 8,
+    };
+
+    #[test]
+    fn corners_at_the_ends_of_i32_do_not_overflow() {
+        let huge = Rect::from_corners((i32::MIN, i32::MIN), (i32::MAX, i32::MAX));
+        assert_eq!((huge.x, huge.right()), (-CORNER_LIMIT, CORNER_LIMIT + 1));
+        assert_eq!(huge.intersect(&SCREEN), SCREEN);
+        let far = Rect::from_corners((5, 5), (i32::MAX, i32::MAX));
+        assert_eq!(far.right(), CORNER_LIMIT + 1);
+        assert_eq!(far.intersect(&SCREEN), Rect::new(5, 5, 5, 3));
+        let edge = Rect::new(i32::MAX - 1, 0, i32::MAX, 1);
+        assert_eq!(edge.right(), i32::MAX);
+        assert_eq!(edge.union(&Rect::new(i32::MIN, 0, 1, 1)).x, i32::MIN);
+        assert!(Rect::from_corners((i32::MIN, 0), (-5, 3)).intersect(&SCREEN).is_empty());
+        assert_eq!(huge.union(&far), huge);
+        assert!(far.contains((CORNER_LIMIT, 6)));
+        assert_eq!(Rect::from_corners((3, 7), (1, 2)), Rect::new(1, 2, 3, 6));
+    }
+
+    #[test]
+    fn lines_inside_are_kept_and_lines_outside_dropped() {
+        assert_eq!(clip_line(&SCREEN, (1, 1), (8, 6)), Some(((1, 1), (8, 6))));
+        assert_eq!(clip_line(&SCREEN, (-5, -1), (20, -1)), None);
+        assert_eq!(clip_line(&SCREEN, (12, 0), (15, 7)), None);
+        assert_eq!(clip_line(&Rect::new(0, 0, 0, 0), (0, 0), (1, 1)), None);
+    }
+
+    #[test]
+    fn lines_crossing_the_edges_end_on_the_last_pixels() {
+        assert_eq!(clip_line(&SCREEN, (-10, 3), (30, 3)), Some(((0, 3), (9, 3))));
+        assert_eq!(clip_line(&SCREEN, (4, -4), (4, 40)), Some(((4, 0), (4, 7))));
+        assert_eq!(clip_line(&SCREEN, (-2, -2), (12, 12)), Some(((0, 0), (7, 7))));
+        // Both ends outside on different sides, clipping the corner pixel
+        assert_eq!(clip_line(&SCREEN, (-1, 5), (5, 20)), Some(((0, 7), (0, 7))));
+        assert_eq!(clip_line(&SCREEN, (-2, 5), (4, 20)), None);
+        let far = clip_line(&SCREEN, (i32::MIN, 4), (i32::MAX, 4));
+        assert_eq!(far, Some(((0, 4), (9, 4))));
+        let diagonal = clip_line(&SCREEN, (i32::MIN, i32::MIN), (i32::MAX, i32::MAX));
+        assert_eq!(diagonal, Some(((0, 0), (7, 7))));
+    }
+
+    #[test]
+    fn float_lines_clip_at_pixel_edges() {
+        assert_eq!(clip_line_f32(&SCREEN, (-5.0, 2.5), (15.0, 2.5)), Some(((0.0, 2.5), (10.0, 2.5))));
+    }
+
+    #[test]
+    fn polygons_are_cut_along_the_edges() {
+        let inside = [(1.0, 1.0), (5.0, 1.0), (3.0, 4.0)];
+        assert_eq!(clip_polygon(&SCREEN, &inside), inside);
+        assert!(clip_polygon(&SCREEN, &[(20.0, 0.0), (30.0, 0.0), (25.0, 5.0)]).is_empty());
+
+        // A square overhanging the right edge becomes the part inside
+        let square = [(6.0, 2.0), (14.0, 2.0), (14.0, 6.0), (6.0, 6.0)];
+        let clipped = clip_polygon(&SCREEN, &square);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|&(x, y)| (6.0..=10.0).contains(&x) && (2.0..=6.0).contains(&y)));
+        assert!(clipped.contains(&(10.0, 2.0)) && clipped.contains(&(10.0, 6.0)));
+
+        // A triangle covering the whole screen clips to its corners
+        let cover = clip_polygon(&SCREEN, &[(-50.0, -50.0), (100.0, -50.0), (-50.0, 100.0)]);
+        for corner in [(0.0, 0.0), (10.0, 0.0), (10.0, 8.0), (0.0, 8.0)] {
+            assert!(cover.contains(&corner), "{corner:?} missing from {cover:?}");
+        }
+    }
+}
@@ -0,0 +1,105 @@
+#![allow(unused)]
+
+use std::time::Duration;
+
+/// The length of one tick at `rate` ticks per second, or `None` when that
+/// is not a positive time a `Duration` can hold.
+pub fn period(rate: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(1.0 / rate).ok().filter(|tick| !tick.is_zero())
+}
+
+/// Turns the real time between frames into a whole number of fixed-length
+/// simulation ticks, so the game runs at the same speed whatever the frame
+/// rate. The time left over is kept for the next frame and tells how far
+/// the drawing should be between the last two ticks.
+pub struct FixedTimestep {
+    tick: Duration,
+    /// Time passed that no tick has covered yet, less than one tick after
+    /// `advance`.
+    pending: Duration,
+    /// Most ticks run by one `advance`. Time beyond that is dropped, so a
+    /// stall slows the game down instead of making it catch up in a burst.
+    pub max_ticks: u32,
+}
+
+impl FixedTimestep {
+    /// A clock running ticks of length `tick`.
+    pub fn new(tick: Duration) -> Self {
+        assert!(!tick.is_zero(), "the tick must be longer than zero");
+        Self {
+            tick,
+            pending: Duration::ZERO,
+            max_ticks: 5,
+        }
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    /// Adds `delta` of real time and returns how many ticks to simulate.
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.pending += delta;
+        let mut ticks = 0;
+        while self.pending >= self.tick {
+            if ticks == self.max_ticks {
+                self.pending = Duration::ZERO;
+                break;
+            }
+            self.pending -= self.tick;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// How far the clock is between the last tick and the next, from 0 to 1.
+    /// Drawing moving things at this fraction of the way from their previous
+    /// to their current position keeps them smooth between ticks.
+    pub fn alpha(&self) -> f32 {
+        self.pending.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut clock = FixedTimestep::new(Duration::from_millis(100));
+        assert_eq!(clock.advance(Duration::from_millis(60)), 0);
+        assert!((clock.alpha() - 0.6).abs() < 1e-6);
+        assert_eq!(clock.advance(Duration::from_millis(60)), 1);
+        assert!((clock.alpha() - 0.2).abs() < 1e-6);
+        assert_eq!(clock.advance(Duration::from_millis(200)), 2);
+    }
+
+    #[test]
+    fn stalls_run_at_most_max_ticks() {
+        let mut clock = FixedTimestep::new(Duration::from_millis(100));
+        assert_eq!(clock.advance(Duration::from_secs(3)), 5);
+        assert_eq!(clock.alpha(), 0.0);
+        assert_eq!(clock.advance(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn max_ticks_is_a_hard_cap() {
+        let mut clock = FixedTimestep::new(Duration::from_millis(100));
+        clock.max_ticks = 0;
+        assert_eq!(clock.advance(Duration::from_secs(1)), 0);
+        assert_eq!(clock.alpha(), 0.0);
+        clock.max_ticks = 1;
+        assert_eq!(clock.advance(Duration::from_millis(150)), 1);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(clock.advance(Duration::from_millis(250)), 1);
+        assert_eq!(clock.alpha(), 0.0);
+    }
+
+    #[test]
+    fn rates_without_a_usable_tick_are_rejected() {
+        assert_eq!(period(4.0), Some(Duration::from_millis(250)));
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-20, 1e300] {
+            assert_eq!(period(rate), None, "rate {rate}");
+        }
+    }
+}
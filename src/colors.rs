@@ -1,24 +1,437 @@
 #![allow(unused)]
 
-use std::ops::{Deref, DerefMut};
-
 use rand::Rng;
 
-pub struct Colors;
+/// An 8-bit sRGB color with straight (not premultiplied) alpha. An alpha of
+/// 255 is fully opaque.
+///
+/// Buffers store pixels packed as `0x00RRGGBB`, and plain `u32` values
+/// convert into opaque colors, so `set_color(0xFF8000)` keeps working.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const GRAY: Color = Color::rgb(128, 128, 128);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+    pub const YELLOW: Color = Color::rgb(255, 255, 0);
+    pub const CYAN: Color = Color::rgb(0, 255, 255);
+    pub const MAGENTA: Color = Color::rgb(255, 0, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 255)
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// An opaque color from a packed `0x00RRGGBB` value; the top byte is
+    /// ignored.
+    pub const fn from_u32(rgb: u32) -> Self {
+        Self::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+
+    /// Packs the color as `0x00RRGGBB`, dropping alpha.
+    pub const fn to_u32(self) -> u32 {
+        ((self.r as u32) << 16) | ((self.g as u32) << 8) | self.b as u32
+    }
+
+    /// A color from a packed `0xAARRGGBB` value.
+    pub const fn from_argb(argb: u32) -> Self {
+        let rgb = Self::from_u32(argb);
+        Self::rgba(rgb.r, rgb.g, rgb.b, (argb >> 24) as u8)
+    }
+
+    pub const fn to_argb(self) -> u32 {
+        ((self.a as u32) << 24) | self.to_u32()
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self::rgba(self.r, self.g, self.b, a)
+    }
+
+    /// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, with or without the
+    /// leading `#`.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        match hex.len() {
+            3 | 4 => {
+                let short = |i| digit(i).map(|d| d * 17);
+                let a = if hex.len() == 4 { short(3)? } else { 255 };
+                Some(Self::rgba(short(0)?, short(1)?, short(2)?, a))
+            }
+            6 | 8 => {
+                let a = if hex.len() == 8 { byte(6)? } else { 255 };
+                Some(Self::rgba(byte(0)?, byte(2)?, byte(4)?, a))
+            }
+            _ => None,
+        }
+    }
+
+    /// Formats as `#rrggbb`, or `#rrggbbaa` when not fully opaque.
+    pub fn to_hex(self) -> String {
+        if self.a == 255 {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    /// Channels as floats in [0, 1], still sRGB encoded.
+    pub fn to_f32(self) -> (f32, f32, f32, f32) {
+        let f = |c: u8| c as f32 / 255.0;
+        (f(self.r), f(self.g), f(self.b), f(self.a))
+    }
+
+    /// Rounds and clamps float channels in [0, 1].
+    pub fn from_f32(r: f32, g: f32, b: f32, a: f32) -> Self {
+        let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self::rgba(c(r), c(g), c(b), c(a))
+    }
+
+    /// An opaque color from hue in degrees, saturation and value in [0, 1].
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let chroma = value * saturation;
+        from_hue_chroma(hue, chroma, value - chroma)
+    }
+
+    /// Hue in degrees [0, 360), saturation and value in [0, 1].
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (r, g, b, _) = self.to_f32();
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+        (hue(r, g, b, max, delta), saturation, max)
+    }
+
+    /// An opaque color from hue in degrees, saturation and lightness in [0, 1].
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        from_hue_chroma(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// Hue in degrees [0, 360), saturation and lightness in [0, 1].
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (r, g, b, _) = self.to_f32();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let lightness = (max + min) / 2.0;
+        let saturation = if delta == 0.0 {
+            0.0
+        } else {
+            delta / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (hue(r, g, b, max, delta), saturation, lightness)
+    }
+
+    /// An opaque color from linear light channels in [0, 1].
+    pub fn from_linear(r: f32, g: f32, b: f32) -> Self {
+        Self::from_f32(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), 1.0)
+    }
+
+    /// RGB decoded to linear light, where averaging is physically meaningful.
+    pub fn to_linear(self) -> (f32, f32, f32) {
+        let (r, g, b, _) = self.to_f32();
+        (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    /// Interpolates every channel, alpha included, in sRGB space. `t` is
+    /// clamped to [0, 1].
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let c = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Self::rgba(c(self.r, other.r), c(self.g, other.g), c(self.b, other.b), c(self.a, other.a))
+    }
+
+    /// Like `lerp`, but mixes RGB in linear light, so a half mix of red and
+    /// green is a bright yellow rather than a muddy one.
+    pub fn mix(self, other: Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let (a, b) = (self.to_linear(), other.to_linear());
+        let c = |a: f32, b: f32| a + (b - a) * t;
+        let alpha = self.lerp(other, t).a;
+        Self::from_linear(c(a.0, b.0), c(a.1, b.1), c(a.2, b.2)).with_alpha(alpha)
+    }
+
+    /// Adds `amount` in [-1, 1] to every RGB channel; -1 is black, 1 white.
+    pub fn adjust_brightness(self, amount: f32) -> Self {
+        let (r, g, b, a) = self.to_f32();
+        Self::from_f32(r + amount, g + amount, b + amount, a)
+    }
+
+    /// Scales the RGB channels' distance from mid gray by `factor`; 0 gives
+    /// flat gray, 1 leaves the color unchanged.
+    pub fn adjust_contrast(self, factor: f32) -> Self {
+        let (r, g, b, a) = self.to_f32();
+        let c = |v: f32| (v - 0.5) * factor + 0.5;
+        Self::from_f32(c(r), c(g), c(b), a)
+    }
+
+    /// A random opaque color.
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        Self::rgb(rng.gen(), rng.gen(), rng.gen())
+    }
+}
+
+impl From<u32> for Color {
+    fn from(rgb: u32) -> Self {
+        Self::from_u32(rgb)
+    }
+}
+
+impl From<Color> for u32 {
+    fn from(color: Color) -> Self {
+        color.to_u32()
+    }
+}
+
+fn from_hue_chroma(hue: f32, chroma: f32, m: f32) -> Color {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::from_f32(r + m, g + m, b + m, 1.0)
+}
+
+fn hue(r: f32, g: f32, b: f32, max: f32, delta: f32) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let hue = if max == r {
+        (g - b) / delta
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    (hue * 60.0).rem_euclid(360.0)
+}
+
+/// Decodes one sRGB channel in [0, 1] to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes one linear light channel in [0, 1] as sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// A named, fixed set of colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: &'static [Color],
+}
+
+impl Palette {
+    /// The 16 colors of the PICO-8 fantasy console.
+    pub const PICO8: Palette = Palette {
+        name: "pico8",
+        colors: &[
+            Color::from_u32(0x000000),
+            Color::from_u32(0x1D2B53),
+            Color::from_u32(0x7E2553),
+            Color::from_u32(0x008751),
+            Color::from_u32(0xAB5236),
+            Color::from_u32(0x5F574F),
+            Color::from_u32(0xC2C3C7),
+            Color::from_u32(0xFFF1E8),
+            Color::from_u32(0xFF004D),
+            Color::from_u32(0xFFA300),
+            Color::from_u32(0xFFEC27),
+            Color::from_u32(0x00E436),
+            Color::from_u32(0x29ADFF),
+            Color::from_u32(0x83769C),
+            Color::from_u32(0xFF77A8),
+            Color::from_u32(0xFFCCAA),
+        ],
+    };
+
+    /// The four greens of the original Game Boy, darkest first.
+    pub const GAMEBOY: Palette = Palette {
+        name: "gameboy",
+        colors: &[
+            Color::from_u32(0x0F380F),
+            Color::from_u32(0x306230),
+            Color::from_u32(0x8BAC0F),
+            Color::from_u32(0x9BBC0F),
+        ],
+    };
+
+    /// CGA mode 4, palette 1 in high intensity.
+    pub const CGA: Palette = Palette {
+        name: "cga",
+        colors: &[
+            Color::from_u32(0x000000),
+            Color::from_u32(0x55FFFF),
+            Color::from_u32(0xFF55FF),
+            Color::from_u32(0xFFFFFF),
+        ],
+    };
+
+    pub const ALL: &'static [Palette] = &[Palette::PICO8, Palette::GAMEBOY, Palette::CGA];
 
-impl Colors {
-    pub const BLACK: u32 = 0;
-    pub const WHITE: u32 = 16777215;
-    pub const GREEN: u32 = 65280;
-    pub const RED: u32 = 16711680;
-    pub const BLUE: u32 = 255;
+    /// Looks up a built-in palette, ignoring case.
+    pub fn by_name(name: &str) -> Option<Palette> {
+        Self::ALL.iter().copied().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The color at `index`, wrapping around past the end.
+    pub fn get(&self, index: usize) -> Color {
+        self.colors[index % self.colors.len()]
+    }
+
+    /// The palette color closest to `color` by RGB distance, keeping its alpha.
+    pub fn nearest(&self, color: Color) -> Color {
+        let distance = |c: &Color| {
+            let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            d(c.r, color.r) + d(c.g, color.g) + d(c.b, color.b)
+        };
+        self.colors
+            .iter()
+            .min_by_key(|c| distance(c))
+            .map_or(color, |c| c.with_alpha(color.a))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32, f32), b: (f32, f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3 && (a.2 - b.2).abs() < 1e-3
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        for hex in ["#000000", "#ff8000", "#1d2b53", "#12345678", "#ffffff00"] {
+            assert_eq!(Color::from_hex(hex).unwrap().to_hex(), hex);
+        }
+        assert_eq!(Color::from_hex("f80"), Some(Color::rgb(0xFF, 0x88, 0x00)));
+        assert_eq!(Color::from_hex("#f808"), Some(Color::rgba(0xFF, 0x88, 0x00, 0x88)));
+        assert_eq!(Color::from_hex("#FF8000FF"), Some(Color::rgb(0xFF, 0x80, 0x00)));
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        for hex in ["", "#", "#12", "#12345", "#1234567", "#123456789", "#ggg", "#12 456", "##123456", "+12345"] {
+            assert_eq!(Color::from_hex(hex), None, "{hex:?}");
+        }
+    }
+
+    #[test]
+    fn hsv_round_trips_on_primaries_and_greys() {
+        let cases = [
+            (Color::RED, (0.0, 1.0, 1.0)),
+            (Color::GREEN, (120.0, 1.0, 1.0)),
+            (Color::BLUE, (240.0, 1.0, 1.0)),
+            (Color::YELLOW, (60.0, 1.0, 1.0)),
+            (Color::CYAN, (180.0, 1.0, 1.0)),
+            (Color::MAGENTA, (300.0, 1.0, 1.0)),
+            (Color::BLACK, (0.0, 0.0, 0.0)),
+            (Color::WHITE, (0.0, 0.0, 1.0)),
+        ];
+        for (color, hsv) in cases {
+            assert!(close(color.to_hsv(), hsv), "{color:?} gave {:?}", color.to_hsv());
+            assert_eq!(Color::from_hsv(hsv.0, hsv.1, hsv.2), color);
+        }
+        for grey in [0, 64, 128, 200, 255] {
+            let color = Color::rgb(grey, grey, grey);
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(s, 0.0);
+            assert_eq!(Color::from_hsv(h, s, v), color);
+        }
+    }
+
+    #[test]
+    fn hsl_round_trips_on_primaries_and_greys() {
+        for color in [Color::RED, Color::GREEN, Color::BLUE, Color::GRAY, Color::WHITE, Color::BLACK] {
+            let (h, s, l) = color.to_hsl();
+            assert_eq!(Color::from_hsl(h, s, l), color);
+        }
+        assert!(close(Color::RED.to_hsl(), (0.0, 1.0, 0.5)));
+        assert!(close(Color::WHITE.to_hsl(), (0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn linear_round_trips_through_srgb() {
+        for value in [0.0, 0.001, 0.0031308, 0.01, 0.2, 0.5, 0.8, 1.0] {
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-5, "{value}");
+        }
+        for c in 0..=255 {
+            let color = Color::rgb(c, c, c);
+            let (r, g, b) = color.to_linear();
+            assert_eq!(Color::from_linear(r, g, b), color);
+        }
+        assert!((Color::GRAY.to_linear().0 - 0.2159).abs() < 1e-3);
+    }
+
+    #[test]
+    fn lerp_and_mix_blend_between_the_ends() {
+        let (from, to) = (Color::rgba(0, 0, 0, 0), Color::WHITE);
+        assert_eq!(from.lerp(to, 0.0), from);
+        assert_eq!(from.lerp(to, 1.0), to);
+        assert_eq!(from.lerp(to, 0.5), Color::rgba(128, 128, 128, 128));
+        assert_eq!(from.lerp(to, 2.0), to);
+        // Half red and half green in linear light is brighter than in sRGB
+        let mixed = Color::RED.mix(Color::GREEN, 0.5);
+        assert_eq!(mixed, Color::rgb(188, 188, 0));
+        assert!(mixed.r > Color::RED.lerp(Color::GREEN, 0.5).r);
+    }
 
-    pub fn from_u8_rgb(r: u8, g: u8, b: u8) -> u32 {
-        let (r, g, b) = (r as u32, g as u32, b as u32);
-        (r << 16) | (g << 8) | b
+    #[test]
+    fn brightness_and_contrast_clamp() {
+        let color = Color::rgba(100, 150, 200, 7);
+        assert_eq!(color.adjust_brightness(1.0), Color::rgba(255, 255, 255, 7));
+        assert_eq!(color.adjust_brightness(-1.0), Color::rgba(0, 0, 0, 7));
+        assert_eq!(color.adjust_brightness(0.0), color);
+        assert_eq!(color.adjust_contrast(1.0), color);
+        assert_eq!(color.adjust_contrast(0.0), Color::rgba(128, 128, 128, 7));
+        assert_eq!(color.adjust_contrast(10.0), Color::rgba(0, 255, 255, 7));
     }
 
-    pub fn random() -> u32 {
-        rand::random()
+    #[test]
+    fn palettes_wrap_and_snap_to_the_nearest_color() {
+        assert_eq!(Palette::by_name("GameBoy"), Some(Palette::GAMEBOY));
+        assert_eq!(Palette::by_name("nes"), None);
+        assert_eq!(Palette::CGA.get(5), Palette::CGA.get(1));
+        assert_eq!(Palette::CGA.nearest(Color::rgba(250, 90, 240, 9)), Color::rgba(0xFF, 0x55, 0xFF, 9));
+        for palette in Palette::ALL {
+            for &color in palette.colors {
+                assert_eq!(palette.nearest(color), color);
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,254 @@
+#![allow(unused)]
+
+use std::rc::Rc;
+
+use crate::animation::Animation;
+use crate::blend::BlendMode;
+use crate::colors::Color;
+use crate::font::TextStyle;
+use crate::linalg::matrix::Mat3x3;
+use crate::sprite::{Blit, Sprite};
+use crate::stroke::StrokeStyle;
+use crate::tilemap::{TileLook, Tilemap};
+
+/// A sprite shared by recordings. Two are equal when they are the same
+/// allocation, so comparing lists never compares pixels; swap in a new `Rc`
+/// when the image changes.
+#[derive(Clone)]
+pub struct SharedSprite(pub Rc<Sprite>);
+
+impl PartialEq for SharedSprite {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// What a recorded command draws, in local coordinates.
+#[derive(Clone, PartialEq)]
+pub enum Shape {
+    /// Covers the whole clip rectangle, see `Canvas::fill`.
+    Fill,
+    /// A filled polygon, see `Canvas::fill_polygon`.
+    Polygon(Vec<(f32, f32)>),
+    /// Lines through the points, see `Canvas::polyline`.
+    Stroke {
+        points: Vec<(f32, f32)>,
+        closed: bool,
+        style: StrokeStyle,
+    },
+    Text {
+        pos: (f32, f32),
+        text: String,
+        style: TextStyle,
+    },
+    Sprite {
+        sprite: SharedSprite,
+        p1: (f32, f32),
+        p2: (f32, f32),
+        blit: Blit,
+    },
+}
+
+/// A shape along with the drawing state it was recorded with.
+#[derive(Clone, PartialEq)]
+pub struct Command {
+    pub shape: Shape,
+    pub color: Color,
+    pub blend_mode: BlendMode,
+    pub transform: Mat3x3<f32>,
+}
+
+/// Drawing commands recorded with the same calls as on `Canvas`, to be
+/// replayed later. Recording a frame instead of drawing it lets
+/// `Canvas::diff` find the screen areas that changed since the previous
+/// frame, so `Canvas::redraw` can skip everything else.
+#[derive(Clone, PartialEq)]
+pub struct DisplayList {
+    commands: Vec<Command>,
+    color: Color,
+    blend_mode: BlendMode,
+    transform: Mat3x3<f32>,
+    transform_stack: Vec<Mat3x3<f32>>,
+    text_style: TextStyle,
+    stroke_style: StrokeStyle,
+}
+
+impl Default for DisplayList {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            color: Color::WHITE,
+            blend_mode: BlendMode::Normal,
+            transform: Mat3x3::identity(),
+            transform_stack: Vec::new(),
+            text_style: TextStyle::default(),
+            stroke_style: StrokeStyle::default(),
+        }
+    }
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    fn record(&mut self, shape: Shape) {
+        self.commands.push(Command {
+            shape,
+            color: self.color,
+            blend_mode: self.blend_mode,
+            transform: self.transform,
+        });
+    }
+
+    /// Records another list's commands as if drawn here, under the current
+    /// transform. Static parts of a scene can be recorded once this way.
+    pub fn append(&mut self, other: &DisplayList) {
+        let transform = self.transform;
+        self.commands.extend(other.commands.iter().map(|command| Command {
+            transform: transform * command.transform,
+            ..command.clone()
+        }));
+    }
+
+    pub fn set_color(&mut self, color: impl Into<Color>) {
+        self.color = color.into();
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.color.a = alpha;
+    }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn set_text_style(&mut self, style: TextStyle) {
+        self.text_style = style;
+    }
+
+    pub fn set_stroke_style(&mut self, style: StrokeStyle) {
+        self.stroke_style = style;
+    }
+
+    pub fn push_transform(&mut self) {
+        self.transform_stack.push(self.transform);
+    }
+
+    pub fn pop_transform(&mut self) {
+        if let Some(transform) = self.transform_stack.pop() {
+            self.transform = transform;
+        }
+    }
+
+    pub fn apply_transform(&mut self, transform: Mat3x3<f32>) {
+        self.transform = self.transform * transform;
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.apply_transform(Mat3x3::translation(x, y));
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.apply_transform(Mat3x3::rotation(angle));
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.apply_transform(Mat3x3::scale(x, y));
+    }
+
+    pub fn fill(&mut self) {
+        self.record(Shape::Fill);
+    }
+
+    fn stroke(&mut self, points: &[(f32, f32)], closed: bool) {
+        self.record(Shape::Stroke {
+            points: points.to_vec(),
+            closed,
+            style: self.stroke_style.clone(),
+        });
+    }
+
+    pub fn line(&mut self, p1: (f32, f32), p2: (f32, f32)) {
+        self.stroke(&[p1, p2], false)
+    }
+
+    pub fn polyline(&mut self, points: &[(f32, f32)]) {
+        self.stroke(points, false)
+    }
+
+    pub fn tri(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        self.stroke(&[p1, p2, p3], true)
+    }
+
+    pub fn sqr(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), p4: (f32, f32)) {
+        self.stroke(&[p1, p2, p3, p4], true)
+    }
+
+    pub fn fill_tri(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        self.fill_polygon(&[p1, p2, p3])
+    }
+
+    pub fn fill_quad(&mut self, p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), p4: (f32, f32)) {
+        self.fill_polygon(&[p1, p2, p3, p4])
+    }
+
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        self.record(Shape::Polygon(points.to_vec()));
+    }
+
+    pub fn text(&mut self, pos: (f32, f32), text: &str) {
+        self.record(Shape::Text {
+            pos,
+            text: text.to_owned(),
+            style: self.text_style,
+        });
+    }
+
+    pub fn draw_sprite(&mut self, sprite: &Rc<Sprite>, p1: (f32, f32), p2: (f32, f32), blit: &Blit) {
+        self.record(Shape::Sprite {
+            sprite: SharedSprite(Rc::clone(sprite)),
+            p1,
+            p2,
+            blit: *blit,
+        });
+    }
+
+    /// Records the animation's current frame, see `Canvas::draw_animation`.
+    pub fn draw_animation(&mut self, sheet: &Rc<Sprite>, animation: &Animation, p1: (f32, f32), p2: (f32, f32), blit: &Blit) {
+        if let Some(source) = animation.frame() {
+            let blit = Blit {
+                source: Some(source),
+                ..*blit
+            };
+            self.draw_sprite(sheet, p1, p2, &blit)
+        }
+    }
+
+    /// Records every non-empty tile as its own command, so a changed tile
+    /// only dirties its own square.
+    pub fn draw_tilemap(&mut self, map: &Tilemap) {
+        let previous = self.color;
+        let (columns, rows) = map.size();
+        for row in 0..rows {
+            for column in 0..columns {
+                let (p1, p2) = map.tile_bounds(column, row);
+                match map.look(map.get(column, row)) {
+                    TileLook::Empty => {}
+                    TileLook::Color(color) => {
+                        self.set_color(color);
+                        self.fill_quad(p1, (p2.0, p1.1), p2, (p1.0, p2.1));
+                    }
+                    TileLook::Sprite(index) => {
+                        self.draw_sprite(map.sprite(index), (p1.0, p2.1), (p2.0, p1.1), &Blit::default())
+                    }
+                }
+            }
+        }
+        self.color = previous;
+    }
+}
@@ -0,0 +1,279 @@
+#![allow(unused)]
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::buffer::Buffer;
+use crate::colors::Color;
+use crate::sprite::Sprite;
+
+/// The most pixels `load_ppm` accepts, so a corrupt header cannot make it
+/// allocate gigabytes before finding the file is short.
+const MAX_PPM_PIXELS: usize = 1 << 26;
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Reads the magic number, width, height and maximum value of a PPM header,
+/// skipping `#` comments. Exactly one whitespace byte after the maximum
+/// value is consumed, so the reader is left at the first pixel byte even
+/// when the pixels follow on the same line.
+fn read_ppm_header(file: &mut impl BufRead) -> io::Result<Vec<String>> {
+    let mut bytes = file.bytes();
+    let mut next = || bytes.next().unwrap_or_else(|| Err(invalid_data("truncated PPM header")));
+    let mut header = Vec::new();
+    let mut field = String::new();
+    while header.len() < 4 {
+        match next()? {
+            b'#' if field.is_empty() => while !matches!(next()?, b'\n' | b'\r') {},
+            byte if byte.is_ascii_whitespace() => {
+                if !field.is_empty() {
+                    header.push(std::mem::take(&mut field));
+                }
+            }
+            byte => field.push(byte as char),
+        }
+    }
+    Ok(header)
+}
+
+impl Buffer {
+    fn rgb_bytes(&self) -> Vec<u8> {
+        self.iter()
+            .flat_map(|&pixel| {
+                let color = Color::from_u32(pixel);
+                [color.r, color.g, color.b]
+            })
+            .collect()
+    }
+
+    fn from_rgb_bytes(width: usize, height: usize, bytes: &[u8]) -> Self {
+        let mut buffer = Buffer::new(width, height);
+        for (pixel, rgb) in buffer.iter_mut().zip(bytes.chunks_exact(3)) {
+            *pixel = Color::rgb(rgb[0], rgb[1], rgb[2]).to_u32();
+        }
+        buffer
+    }
+
+    /// Writes the buffer as an 8-bit RGB PNG.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (width, height) = self.size();
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(invalid_data)?;
+        writer
+            .write_image_data(&self.rgb_bytes())
+            .map_err(invalid_data)
+    }
+
+    /// Writes the buffer as a binary (P6) PPM.
+    pub fn save_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (width, height) = self.size();
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "P6\n{width} {height}\n255\n")?;
+        file.write_all(&self.rgb_bytes())?;
+        file.flush()
+    }
+
+    /// Loads a PNG, discarding its alpha channel.
+    pub fn load_png(path: impl AsRef<Path>) -> io::Result<Self> {
+        let sprite = Sprite::load(path)?;
+        let (width, height) = sprite.size();
+        let mut buffer = Buffer::new(width, height);
+        for (pixel, &argb) in buffer.iter_mut().zip(sprite.pixels()) {
+            *pixel = argb & 0x00FF_FFFF;
+        }
+        Ok(buffer)
+    }
+
+    /// Loads a binary (P6) PPM with a maximum value of 255.
+    pub fn load_ppm(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let header = read_ppm_header(&mut file)?;
+        if header[0] != "P6" {
+            return Err(invalid_data(format!("unsupported PPM magic {:?}", header[0])));
+        }
+        let parse = |field: &str| field.parse::<usize>().map_err(invalid_data);
+        let (width, height, max) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+        if max != 255 {
+            return Err(invalid_data(format!("unsupported PPM max value {max}")));
+        }
+        let pixels = width
+            .checked_mul(height)
+            .filter(|&pixels| pixels <= MAX_PPM_PIXELS)
+            .ok_or_else(|| invalid_data(format!("PPM size {width}x{height} is too large")))?;
+        let mut bytes = vec![0; pixels * 3];
+        file.read_exact(&mut bytes)?;
+        Ok(Buffer::from_rgb_bytes(width, height, &bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("export-{}-{name}", std::process::id()))
+    }
+
+    fn gradient() -> Buffer {
+        let mut buffer = Buffer::new(5, 3);
+        for (i, pixel) in buffer.iter_mut().enumerate() {
+            *pixel = Color::rgb(i as u8 * 17, 255 - i as u8, (i as u8).wrapping_mul(91)).to_u32();
+        }
+        buffer
+    }
+
+    /// Compares `buffer` with the reference image `golden/<name>.png`. Run
+    /// the tests with `UPDATE_GOLDEN` set to write the references instead,
+    /// after checking the change in the drawing is wanted.
+    fn assert_golden(name: &str, buffer: &Buffer) {
+        let path = Path::new("golden").join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            buffer.save_png(&path).unwrap();
+            return;
+        }
+        let expected = Buffer::load_png(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(buffer.size(), expected.size(), "{name} changed size");
+        let differing = buffer.iter().zip(expected.iter()).filter(|(a, b)| a != b).count();
+        if differing > 0 {
+            let actual = temp_path(&format!("{name}.png"));
+            buffer.save_png(&actual).unwrap();
+            panic!("{differing} pixels of {name} differ from {}, drawn to {}", path.display(), actual.display());
+        }
+    }
+
+    /// A 48x48 buffer with three colors of `draw`'s shapes, some reaching
+    /// past the edges.
+    fn golden_shapes(draw: impl Fn(&mut Buffer, i32)) -> Buffer {
+        let mut buffer = Buffer::new(48, 48);
+        for (i, color) in [Color::RED, Color::GREEN, Color::WHITE].into_iter().enumerate() {
+            buffer.set_color(color);
+            draw(&mut buffer, i as i32);
+        }
+        buffer
+    }
+
+    #[test]
+    fn lines_match_golden_image() {
+        assert_golden(
+            "line",
+            &golden_shapes(|b, i| {
+                for end in (-8..56).step_by(9) {
+                    b.line((24, 24), (end + i, -4 + 60 * (i % 2)));
+                    b.line((-6 + 3 * i, end), (53 - i, 47 - end));
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn triangles_match_golden_image() {
+        assert_golden(
+            "tri",
+            &golden_shapes(|b, i| {
+                b.tri((2 + 5 * i, 3), (45 - 7 * i, 10 + 9 * i), (11, 44 - 6 * i));
+                b.tri((30, -10 + 8 * i), (60, 20), (20 + 6 * i, 40));
+            }),
+        );
+    }
+
+    #[test]
+    fn squares_match_golden_image() {
+        assert_golden(
+            "sqr",
+            &golden_shapes(|b, i| {
+                let (x, y) = (4 + 7 * i, 4 + 5 * i);
+                b.sqr((x, y), (x + 30, y), (x + 30, y + 30), (x, y + 30));
+                b.sqr((24, 2 + 4 * i), (46 + 8 * i, 24), (24, 46 - 4 * i), (2 - 8 * i, 24));
+            }),
+        );
+    }
+
+    #[test]
+    fn level_matches_golden_image() {
+        use crate::camera::Camera2D;
+        use crate::canvas::Canvas;
+        use crate::level::Level;
+        use crate::theme::Theme;
+
+        // Drawn the way ty draws terrain and the goal flag, in cells scaled
+        // so the larger side of the grid plus one spans the view
+        let level = Level::load("levels/01-floor.txt").unwrap();
+        let collisions = level.collisions(0);
+        let (columns, rows) = (collisions.width() as f32, collisions.height() as f32);
+        let mut canvas = Canvas::new(160, 90);
+        canvas.set_camera(Some(Camera2D::new(2.0, 2.0)));
+        let cell = 2.0 / (columns.max(rows) + 1.0);
+        canvas.scale(cell, cell);
+        canvas.translate(-(columns + 1.0) / 2.0, -(rows + 1.0) / 2.0);
+        canvas.draw_tilemap(&Theme::CLASSIC.terrain_tiles(&collisions));
+        canvas.set_color(Theme::CLASSIC.goal);
+        canvas.translate(level.goal.0 as f32, (level.goal.1 + 1) as f32);
+        canvas.fill_tri((1.5, 0.3), (1.0, 0.9), (0.5, 0.3));
+        assert_golden("level-1", canvas.as_buffer());
+    }
+
+    #[test]
+    fn png_round_trips() {
+        let path = temp_path("round-trip.png");
+        let buffer = gradient();
+        buffer.save_png(&path).unwrap();
+        let loaded = Buffer::load_png(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.size(), buffer.size());
+        assert_eq!(*loaded, *buffer);
+    }
+
+    #[test]
+    fn ppm_round_trips() {
+        let path = temp_path("round-trip.ppm");
+        let buffer = gradient();
+        buffer.save_ppm(&path).unwrap();
+        let loaded = Buffer::load_ppm(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.size(), buffer.size());
+        assert_eq!(*loaded, *buffer);
+    }
+
+    #[test]
+    fn ppm_pixels_may_follow_the_header_on_the_same_line() {
+        // The first pixel is a newline byte, which a line based reader
+        // would take for the end of the header
+        let mut header = b"P6 # comment\n2 1 255 ".to_vec();
+        header.extend([10, 20, 30, 40, 50, 60]);
+        let path = temp_path("same-line.ppm");
+        std::fs::write(&path, &header).unwrap();
+        let loaded = Buffer::load_ppm(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(*loaded, [Color::rgb(10, 20, 30).to_u32(), Color::rgb(40, 50, 60).to_u32()]);
+    }
+
+    #[test]
+    fn ppm_rejects_other_formats() {
+        let path = temp_path("invalid.ppm");
+        for contents in [&b"P3 1 1 255 \x01\x02\x03"[..], b"P6 1 1 65535 \0\0\0\0\0\0", b"P6 1 1", b"P6 1 1 255 \x01"] {
+            std::fs::write(&path, contents).unwrap();
+            assert!(Buffer::load_ppm(&path).is_err(), "{:?}", String::from_utf8_lossy(contents));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ppm_rejects_huge_sizes() {
+        let path = temp_path("huge.ppm");
+        std::fs::write(&path, b"P6 99999999 99999999 255 \0\0\0").unwrap();
+        let loaded = Buffer::load_ppm(&path);
+        std::fs::remove_file(&path).unwrap();
+        let error = loaded.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("too large"), "{error}");
+    }
+}
@@ -0,0 +1,211 @@
+#![allow(unused)]
+
+use crate::colors::Color;
+
+/// Width of a glyph in font pixels.
+pub const GLYPH_WIDTH: usize = 5;
+/// Height of a glyph in font pixels, including the descender row.
+pub const GLYPH_HEIGHT: usize = 8;
+/// Horizontal distance between the starts of two consecutive glyphs.
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Vertical distance between two lines of text.
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Size of one font pixel in buffer pixels.
+    pub scale: usize,
+    /// Which edge of each line the anchor position refers to.
+    pub align: Align,
+    /// Overrides the buffer's current color when set.
+    pub color: Option<Color>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            align: Align::Left,
+            color: None,
+        }
+    }
+}
+
+/// Returns the glyph columns for `c`, least significant bit at the top.
+/// Characters outside printable ASCII render as `?`.
+pub fn glyph(c: char) -> &'static [u8; GLYPH_WIDTH] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &GLYPHS[index]
+}
+
+/// Size in buffer pixels of `text` laid out at the given scale.
+pub fn measure(text: &str, scale: usize) -> (usize, usize) {
+    let lines = text.split('\n');
+    let width = lines.clone().map(line_width).max().unwrap_or(0);
+    let height = lines.count() * LINE_HEIGHT - (LINE_HEIGHT - GLYPH_HEIGHT);
+    (width * scale, height * scale)
+}
+
+/// Width in font pixels of a single line, without trailing spacing.
+pub fn line_width(line: &str) -> usize {
+    match line.chars().count() {
+        0 => 0,
+        n => n * ADVANCE - 1,
+    }
+}
+
+/// Classic 5x8 column-major font covering `' '..='~'`.
+static GLYPHS: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x56, 0x20, 0x50], // '&'
+    [0x00, 0x08, 0x07, 0x03, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x80, 0x70, 0x30, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x00, 0x60, 0x60, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x72, 0x49, 0x49, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x49, 0x4D, 0x33], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x31], // '6'
+    [0x41, 0x21, 0x11, 0x09, 0x07], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x46, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x00, 0x14, 0x00, 0x00], // ':'
+    [0x00, 0x40, 0x34, 0x00, 0x00], // ';'
+    [0x00, 0x08, 0x14, 0x22, 0x41], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x59, 0x09, 0x06], // '?'
+    [0x3E, 0x41, 0x5D, 0x59, 0x4E], // '@'
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x41, 0x51, 0x73], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x26, 0x49, 0x49, 0x49, 0x32], // 'S'
+    [0x03, 0x01, 0x7F, 0x01, 0x03], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x03, 0x04, 0x78, 0x04, 0x03], // 'Y'
+    [0x61, 0x59, 0x49, 0x4D, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x41], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x41, 0x7F], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x03, 0x07, 0x08, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x78, 0x40], // 'a'
+    [0x7F, 0x28, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x28], // 'c'
+    [0x38, 0x44, 0x44, 0x28, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x00, 0x08, 0x7E, 0x09, 0x02], // 'f'
+    [0x18, 0xA4, 0xA4, 0x9C, 0x78], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x40, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x78, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0xFC, 0x18, 0x24, 0x24, 0x18], // 'p'
+    [0x18, 0x24, 0x24, 0x18, 0xFC], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x24], // 's'
+    [0x04, 0x04, 0x3F, 0x44, 0x24], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x4C, 0x90, 0x90, 0x90, 0x7C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x77, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x02, 0x01, 0x02, 0x04, 0x02], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    /// Lit pixels of `buffer` as `(x, y)` pairs.
+    fn lit(buffer: &Buffer) -> Vec<(usize, usize)> {
+        let (width, height) = buffer.size();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&p| buffer[p] != 0)
+            .collect()
+    }
+
+    #[test]
+    fn measures_lines_without_trailing_spacing() {
+        assert_eq!(measure("", 1), (0, GLYPH_HEIGHT));
+        assert_eq!(measure("ab\ncde", 2), (2 * 17, 2 * 18));
+        assert_eq!(glyph('é'), glyph('?'));
+    }
+
+    #[test]
+    fn text_draws_glyph_bits() {
+        let mut buffer = Buffer::new(8, 10);
+        buffer.text((1, 1), "!", &TextStyle::default());
+        let column: Vec<_> = [1, 2, 3, 4, 5, 7].iter().map(|&y| (3, y)).collect();
+        assert_eq!(lit(&buffer), column);
+    }
+
+    #[test]
+    fn text_scales_aligns_and_restores_color() {
+        let mut buffer = Buffer::new(16, 20);
+        buffer.set_color(Color::BLUE);
+        let style = TextStyle {
+            scale: 2,
+            align: Align::Right,
+            color: Some(Color::RED),
+        };
+        buffer.text((12, 0), "!", &style);
+        let dots = lit(&buffer);
+        assert_eq!((dots.len(), dots[0], dots[3]), (6 * 4, (6, 0), (7, 1)));
+        assert!(dots.iter().all(|&p| buffer[p] == Color::RED.to_u32()));
+        assert_eq!(buffer.color(), Color::BLUE);
+    }
+}
@@ -0,0 +1,98 @@
+#![allow(unused)]
+
+/// What a cell of a `CollisionGrid` does to the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cell {
+    #[default]
+    Empty,
+    Solid,
+    /// Passable, but entering it kills.
+    Kill,
+}
+
+/// Cells addressed as `(column, row)` with row 0 at the bottom. Lookups
+/// outside the grid never panic; they return the `outside` cell instead, so
+/// the edges can act as walls, open space or a deadly drop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    /// What every cell beyond the edges is.
+    pub outside: Cell,
+}
+
+impl CollisionGrid {
+    /// An empty grid.
+    pub fn new(width: usize, height: usize, outside: Cell) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::Empty; width * height],
+            outside,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, (x, y): (isize, isize)) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn get(&self, (x, y): (isize, isize)) -> Cell {
+        if self.contains((x, y)) {
+            self.cells[y as usize * self.width + x as usize]
+        } else {
+            self.outside
+        }
+    }
+
+    /// Changes a cell inside the grid; cells outside are ignored.
+    pub fn set(&mut self, (x, y): (isize, isize), cell: Cell) {
+        if self.contains((x, y)) {
+            self.cells[y as usize * self.width + x as usize] = cell;
+        }
+    }
+
+    pub fn is_solid(&self, x: isize, y: isize) -> bool {
+        self.get((x, y)) == Cell::Solid
+    }
+
+    pub fn is_deadly(&self, x: isize, y: isize) -> bool {
+        self.get((x, y)) == Cell::Kill
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outside_cells_follow_the_policy() {
+        for policy in [Cell::Empty, Cell::Solid, Cell::Kill] {
+            let grid = CollisionGrid::new(3, 2, policy);
+            for cell in [(-1, 0), (0, -1), (3, 0), (0, 2), (isize::MIN, isize::MAX)] {
+                assert_eq!(grid.get(cell), policy);
+            }
+            assert_eq!(grid.get((2, 1)), Cell::Empty);
+        }
+    }
+
+    #[test]
+    fn set_ignores_cells_outside() {
+        let mut grid = CollisionGrid::new(3, 2, Cell::Empty);
+        grid.set((1, 1), Cell::Solid);
+        grid.set((0, 0), Cell::Kill);
+        grid.set((3, 1), Cell::Solid);
+        grid.set((-1, 0), Cell::Kill);
+        assert!(grid.is_solid(1, 1) && !grid.is_solid(1, 0));
+        assert!(grid.is_deadly(0, 0) && !grid.is_deadly(-1, 0));
+        assert!(!grid.is_solid(3, 1));
+    }
+}
@@ -0,0 +1,285 @@
+#![allow(unused)]
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::grid::{Cell, CollisionGrid};
+
+/// A block of solid cells that slides back and forth along a straight line,
+/// one cell per tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// Bottom-left cell at the start of the path.
+    pub start: (isize, isize),
+    /// Columns and rows covered.
+    pub size: (usize, usize),
+    /// Cells moved per step.
+    pub step: (isize, isize),
+    /// Steps to the far end of the path, after which it heads back.
+    pub steps: usize,
+}
+
+impl Platform {
+    /// Bottom-left cell `tick` ticks after the level started.
+    pub fn position(&self, tick: usize) -> (isize, isize) {
+        let period = 2 * self.steps;
+        let along = match period {
+            0 => 0,
+            _ => {
+                let t = tick % period;
+                t.min(period - t)
+            }
+        } as isize;
+        (self.start.0 + self.step.0 * along, self.start.1 + self.step.1 * along)
+    }
+
+    fn cells(&self, tick: usize) -> impl Iterator<Item = (isize, isize)> {
+        let (x, y) = self.position(tick);
+        let (width, height) = (self.size.0 as isize, self.size.1 as isize);
+        (0..height).flat_map(move |dy| (0..width).map(move |dx| (x + dx, y + dy)))
+    }
+}
+
+/// One level of the `ty` game. Cells are addressed as `(column, row)` with
+/// row 0 at the bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    /// File name without the extension.
+    pub name: String,
+    /// The map without its platforms.
+    pub grid: CollisionGrid,
+    pub spawn: (isize, isize),
+    /// Reaching this cell finishes the level.
+    pub goal: (isize, isize),
+    pub platforms: Vec<Platform>,
+}
+
+impl Level {
+    /// Loads every `.txt` file in `dir`, ordered by file name.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<Self>> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "txt"));
+        paths.sort();
+        paths.iter().map(Self::load).collect()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut level = Self::parse(&fs::read_to_string(path)?).map_err(|(line, message)| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{line}: {message}", path.display()),
+            )
+        })?;
+        level.name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        Ok(level)
+    }
+
+    /// Parses a level. Lines starting with `;` are comments. After a line
+    /// reading `map`, each line is one row of the grid, top row first:
+    /// `#` is solid, `.` empty, `!` kills, and `S` marks the spawn and `G`
+    /// the goal, both empty. Before the map,
+    ///
+    /// `platform <column> <row> <width> <height> <dx> <dy> <steps>`
+    ///
+    /// adds a moving platform whose bottom-left cell starts at
+    /// `(column, row)` and moves `(dx, dy)` per tick for `steps` ticks
+    /// before turning around, and `outside <empty|solid|kill>` sets what
+    /// lies beyond the edges of the map, `kill` by default. Errors carry the
+    /// 1-based line number.
+    pub fn parse(source: &str) -> Result<Self, (usize, String)> {
+        let mut platforms = Vec::new();
+        let mut outside = Cell::Kill;
+        let mut rows: Vec<(usize, &str)> = Vec::new();
+        let mut in_map = false;
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim_end();
+            if line.trim_start().starts_with(';') || (!in_map && line.trim().is_empty()) {
+                continue;
+            }
+            if in_map {
+                rows.push((number, line));
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("map") => in_map = true,
+                Some("platform") => {
+                    let values: Vec<isize> = fields
+                        .map(|field| field.parse().map_err(|_| (number, format!("invalid number {field:?}"))))
+                        .collect::<Result<_, _>>()?;
+                    let [column, row, width, height, dx, dy, steps] = values[..] else {
+                        return Err((number, "expected 7 numbers: column row width height dx dy steps".to_owned()));
+                    };
+                    if width < 1 || height < 1 || steps < 0 {
+                        return Err((number, "platform size must be positive and steps not negative".to_owned()));
+                    }
+                    platforms.push((
+                        number,
+                        Platform {
+                            start: (column, row),
+                            size: (width as usize, height as usize),
+                            step: (dx, dy),
+                            steps: steps as usize,
+                        },
+                    ));
+                }
+                Some("outside") => {
+                    outside = match (fields.next(), fields.next()) {
+                        (Some("empty"), None) => Cell::Empty,
+                        (Some("solid"), None) => Cell::Solid,
+                        (Some("kill"), None) => Cell::Kill,
+                        _ => return Err((number, "expected one of empty, solid or kill".to_owned())),
+                    };
+                }
+                Some(keyword) => return Err((number, format!("unknown keyword {keyword:?}"))),
+                None => {}
+            }
+        }
+
+        let last_line = source.lines().count();
+        // Trailing blank lines aren't rows
+        while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+            rows.pop();
+        }
+        let Some(&(_, first)) = rows.first() else {
+            return Err((last_line, "missing map".to_owned()));
+        };
+        let (width, height) = (first.chars().count(), rows.len());
+        let mut grid = CollisionGrid::new(width, height, outside);
+        let (mut spawn, mut goal) = (None, None);
+        for (index, &(number, line)) in rows.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err((number, format!("expected {width} cells like the first row, found {}", line.chars().count())));
+            }
+            let row = height - 1 - index;
+            for (column, c) in line.chars().enumerate() {
+                let cell = (column as isize, row as isize);
+                let marker = match c {
+                    '#' | '!' => {
+                        grid.set(cell, if c == '#' { Cell::Solid } else { Cell::Kill });
+                        continue;
+                    }
+                    '.' => continue,
+                    'S' => &mut spawn,
+                    'G' => &mut goal,
+                    _ => return Err((number, format!("unknown cell {c:?}, expected one of # . ! S G"))),
+                };
+                if marker.replace(cell).is_some() {
+                    return Err((number, format!("more than one {c:?}")));
+                }
+            }
+        }
+        let spawn = spawn.ok_or((last_line, "the map has no spawn 'S'".to_owned()))?;
+        let goal = goal.ok_or((last_line, "the map has no goal 'G'".to_owned()))?;
+
+        for &(number, platform) in &platforms {
+            let ends = [0, platform.steps].map(|tick| platform.cells(tick).all(|cell| grid.contains(cell)));
+            if !ends.iter().all(|&ok| ok) {
+                return Err((number, "platform leaves the map".to_owned()));
+            }
+        }
+
+        Ok(Self {
+            name: String::new(),
+            grid,
+            spawn,
+            goal,
+            platforms: platforms.into_iter().map(|(_, platform)| platform).collect(),
+        })
+    }
+
+    /// The map with the platforms where they are `tick` ticks after the
+    /// level started.
+    pub fn collisions(&self, tick: usize) -> CollisionGrid {
+        let mut grid = self.grid.clone();
+        for platform in &self.platforms {
+            for cell in platform.cells(tick) {
+                grid.set(cell, Cell::Solid);
+            }
+        }
+        grid
+    }
+
+    /// Whether a platform moving straight up and down passes through the
+    /// column. Standing on solid ground there rides the lift upwards.
+    pub fn in_lift_shaft(&self, column: isize) -> bool {
+        self.platforms.iter().any(|platform| {
+            let x = platform.start.0;
+            platform.step.0 == 0 && platform.step.1 != 0 && (x..x + platform.size.0 as isize).contains(&column)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "map\n#...\n#S.G\n####\n";
+
+    fn error_line(source: &str) -> usize {
+        Level::parse(source).unwrap_err().0
+    }
+
+    #[test]
+    fn shipped_levels_load_in_order() {
+        let levels = Level::load_dir("levels").unwrap();
+        let names: Vec<_> = levels.iter().map(|level| level.name.as_str()).collect();
+        assert_eq!(names, ["01-floor", "02-steps", "03-platforms"]);
+        for level in &levels {
+            assert!(!level.grid.is_solid(level.spawn.0, level.spawn.1));
+            assert!(!level.grid.is_solid(level.goal.0, level.goal.1));
+        }
+    }
+
+    #[test]
+    fn map_rows_run_from_the_top() {
+        let level = Level::parse(&format!("; comment\noutside solid\n{MAP}")).unwrap();
+        assert_eq!((level.grid.width(), level.grid.height()), (4, 3));
+        assert_eq!((level.spawn, level.goal), ((1, 1), (3, 1)));
+        assert!(level.grid.is_solid(0, 2) && !level.grid.is_solid(1, 2));
+        assert_eq!(level.grid.get((4, 0)), Cell::Solid);
+        assert_eq!(Level::parse(MAP).unwrap().grid.get((-1, 0)), Cell::Kill);
+    }
+
+    #[test]
+    fn platforms_turn_around_at_the_end_of_their_path() {
+        let level = Level::parse(&format!("platform 1 2 2 1 1 0 1\n{MAP}")).unwrap();
+        let positions: Vec<_> = (0..4).map(|tick| level.platforms[0].position(tick)).collect();
+        assert_eq!(positions, [(1, 2), (2, 2), (1, 2), (2, 2)]);
+        assert!(level.collisions(1).is_solid(3, 2) && !level.collisions(0).is_solid(3, 2));
+        assert!(!level.grid.is_solid(1, 2));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(error_line("\nlift 1 2\nmap\nSG"), 2);
+        assert_eq!(error_line("platform 1 2 3\nmap\nSG"), 1);
+        assert_eq!(error_line("platform 1 2 x 1 1 0 1\nmap\nSG"), 1);
+        assert_eq!(error_line("platform 0 0 0 1 1 0 1\nmap\nSG"), 1);
+        assert_eq!(error_line("outside lava\nmap\nSG"), 1);
+        assert_eq!(error_line("map\nS..\n..\nG.."), 3);
+        assert_eq!(error_line("map\nS.?G"), 2);
+        assert_eq!(error_line("map\nSG\nS."), 3);
+        assert_eq!(error_line("map\nSG\n.G"), 3);
+        assert_eq!(error_line("; nothing\n\n"), 2);
+        assert_eq!(error_line("map\n"), 1);
+        assert_eq!(error_line("map\n..G\n"), 2);
+        assert_eq!(error_line("map\nS..\n...\n"), 3);
+        assert_eq!(error_line(&format!("\nplatform 2 2 2 1 1 0 1\n{MAP}")), 2);
+    }
+
+    #[test]
+    fn load_errors_name_the_file_and_line() {
+        let path = std::env::temp_dir().join(format!("level-error-{}.txt", std::process::id()));
+        fs::write(&path, "map\nS.\n.G.\n").unwrap();
+        let error = Level::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), format!("{}:3: expected 2 cells like the first row, found 3", path.display()));
+    }
+}
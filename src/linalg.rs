@@ -1,4 +1,142 @@
+/// Implements the operations shared by every vector type: construction,
+/// dot product, length, normalization, interpolation and the arithmetic
+/// operators (component-wise with vectors, uniform with scalars).
+macro_rules! impl_vector {
+    ($name:ident { $($field:ident),+ }) => {
+        impl<T: num::Float> $name<T> {
+            pub fn new($($field: T),+) -> Self {
+                Self { $($field),+ }
+            }
+
+            pub fn splat(value: T) -> Self {
+                Self { $($field: value),+ }
+            }
+
+            pub fn zero() -> Self {
+                Self::splat(T::zero())
+            }
+
+            pub fn dot(self, other: Self) -> T {
+                T::zero() $(+ self.$field * other.$field)+
+            }
+
+            pub fn length_squared(self) -> T {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> T {
+                self.length_squared().sqrt()
+            }
+
+            pub fn distance(self, other: Self) -> T {
+                (other - self).length()
+            }
+
+            /// Scales the vector to unit length. The zero vector is returned unchanged.
+            pub fn normalize(self) -> Self {
+                let length = self.length();
+                if length == T::zero() {
+                    self
+                } else {
+                    self / length
+                }
+            }
+
+            pub fn lerp(self, other: Self, t: T) -> Self {
+                self + (other - self) * t
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self { $($field: self.$field.max(other.$field)),+ }
+            }
+        }
+
+        impl<T: num::Float> Default for $name<T> {
+            fn default() -> Self {
+                Self::zero()
+            }
+        }
+
+        impl<T: num::Float> std::ops::Add for $name<T> {
+            type Output = Self;
+            fn add(self, rhs: Self) -> Self {
+                Self { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Sub for $name<T> {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self {
+                Self { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Mul for $name<T> {
+            type Output = Self;
+            fn mul(self, rhs: Self) -> Self {
+                Self { $($field: self.$field * rhs.$field),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Div for $name<T> {
+            type Output = Self;
+            fn div(self, rhs: Self) -> Self {
+                Self { $($field: self.$field / rhs.$field),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Mul<T> for $name<T> {
+            type Output = Self;
+            fn mul(self, rhs: T) -> Self {
+                Self { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Div<T> for $name<T> {
+            type Output = Self;
+            fn div(self, rhs: T) -> Self {
+                Self { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::Neg for $name<T> {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self { $($field: -self.$field),+ }
+            }
+        }
+
+        impl<T: num::Float> std::ops::AddAssign for $name<T> {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl<T: num::Float> std::ops::SubAssign for $name<T> {
+            fn sub_assign(&mut self, rhs: Self) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl<T: num::Float> std::ops::MulAssign<T> for $name<T> {
+            fn mul_assign(&mut self, rhs: T) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl<T: num::Float> std::ops::DivAssign<T> for $name<T> {
+            fn div_assign(&mut self, rhs: T) {
+                *self = *self / rhs;
+            }
+        }
+    };
+}
+
 pub mod matrix;
 pub mod vec2;
 pub mod vec3;
-pub mod vec4;
\ No newline at end of file
+pub mod vec4;
@@ -0,0 +1,494 @@
+#![allow(unused)]
+
+use std::ops::Mul;
+
+use num::Float;
+
+use super::vec2::Vec2;
+use super::vec3::Vec3;
+use super::vec4::Vec4;
+
+/// A row-major 3x3 matrix, mostly used for 2D affine transforms acting on
+/// column vectors `(x, y, 1)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3x3<T> {
+    pub m: [[T; 3]; 3],
+}
+
+/// A row-major 4x4 matrix acting on column vectors `(x, y, z, w)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4x4<T> {
+    pub m: [[T; 4]; 4],
+}
+
+fn identity<T: Float, const N: usize>() -> [[T; N]; N] {
+    let mut m = [[T::zero(); N]; N];
+    for (i, row) in m.iter_mut().enumerate() {
+        row[i] = T::one();
+    }
+    m
+}
+
+fn multiply<T: Float, const N: usize>(a: &[[T; N]; N], b: &[[T; N]; N]) -> [[T; N]; N] {
+    let mut m = [[T::zero(); N]; N];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..N).fold(T::zero(), |sum, k| sum + a[i][k] * b[k][j]);
+        }
+    }
+    m
+}
+
+fn transpose<T: Float, const N: usize>(a: &[[T; N]; N]) -> [[T; N]; N] {
+    let mut m = *a;
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = a[j][i];
+        }
+    }
+    m
+}
+
+/// The row at or below `col` with the largest entry in column `col`. A NaN
+/// wins, so it carries through to the result instead of being eliminated.
+fn pivot_row<T: Float, const N: usize>(m: &[[T; N]; N], col: usize) -> usize {
+    (col + 1..N).fold(col, |best, row| {
+        if m[row][col].is_nan() || m[row][col].abs() > m[best][col].abs() {
+            row
+        } else {
+            best
+        }
+    })
+}
+
+/// Gaussian elimination with partial pivoting.
+fn determinant<T: Float, const N: usize>(a: &[[T; N]; N]) -> T {
+    let mut m = *a;
+    let mut det = T::one();
+    for col in 0..N {
+        let pivot = pivot_row(&m, col);
+        if m[pivot][col] == T::zero() {
+            return T::zero();
+        }
+        if pivot != col {
+            m.swap(pivot, col);
+            det = -det;
+        }
+        det = det * m[col][col];
+        let pivot_row = m[col];
+        for row in m.iter_mut().skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            for (value, &pivot) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value = *value - factor * pivot;
+            }
+        }
+    }
+    det
+}
+
+/// Gauss-Jordan elimination with partial pivoting. Returns `None` for
+/// singular matrices and matrices containing NaN.
+fn inverse<T: Float, const N: usize>(a: &[[T; N]; N]) -> Option<[[T; N]; N]> {
+    // Pivots this small next to the rest of their column are rounding
+    // error left over from eliminating a dependent row
+    let size = T::from(N).unwrap();
+    let tolerance: [T; N] = std::array::from_fn(|col| {
+        let largest = a.iter().fold(T::zero(), |max, row| max.max(row[col].abs()));
+        largest * size * T::epsilon()
+    });
+    let mut m = *a;
+    let mut inv = identity::<T, N>();
+    for col in 0..N {
+        let pivot = pivot_row(&m, col);
+        if m[pivot][col].is_nan() || m[pivot][col].abs() <= tolerance[col] {
+            return None;
+        }
+        m.swap(pivot, col);
+        inv.swap(pivot, col);
+        let scale = m[col][col];
+        for k in 0..N {
+            m[col][k] = m[col][k] / scale;
+            inv[col][k] = inv[col][k] / scale;
+        }
+        for row in 0..N {
+            if row == col {
+                continue;
+            }
+            let factor = m[row][col];
+            for k in 0..N {
+                m[row][k] = m[row][k] - factor * m[col][k];
+                inv[row][k] = inv[row][k] - factor * inv[col][k];
+            }
+        }
+    }
+    Some(inv)
+}
+
+impl<T: Float> Mat3x3<T> {
+    pub fn from_rows(m: [[T; 3]; 3]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self { m: identity() }
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            m: transpose(&self.m),
+        }
+    }
+
+    pub fn determinant(&self) -> T {
+        determinant(&self.m)
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        inverse(&self.m).map(|m| Self { m })
+    }
+
+    pub fn translation(x: T, y: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        Self::from_rows([[l, o, x], [o, l, y], [o, o, l]])
+    }
+
+    /// Counter-clockwise rotation by `angle` radians.
+    pub fn rotation(angle: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let (sin, cos) = angle.sin_cos();
+        Self::from_rows([[cos, -sin, o], [sin, cos, o], [o, o, l]])
+    }
+
+    pub fn scale(x: T, y: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        Self::from_rows([[x, o, o], [o, y, o], [o, o, l]])
+    }
+
+    /// Applies the full affine transform, including translation.
+    pub fn transform_point(&self, p: Vec2<T>) -> Vec2<T> {
+        let v = *self * Vec3::new(p.x, p.y, T::one());
+        Vec2::new(v.x, v.y)
+    }
+
+    /// Applies only the linear part, ignoring translation.
+    pub fn transform_vector(&self, v: Vec2<T>) -> Vec2<T> {
+        let v = *self * Vec3::new(v.x, v.y, T::zero());
+        Vec2::new(v.x, v.y)
+    }
+}
+
+impl<T: Float> Mat4x4<T> {
+    pub fn from_rows(m: [[T; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self { m: identity() }
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self {
+            m: transpose(&self.m),
+        }
+    }
+
+    pub fn determinant(&self) -> T {
+        determinant(&self.m)
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        inverse(&self.m).map(|m| Self { m })
+    }
+
+    pub fn translation(x: T, y: T, z: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        Self::from_rows([[l, o, o, x], [o, l, o, y], [o, o, l, z], [o, o, o, l]])
+    }
+
+    pub fn scale(x: T, y: T, z: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        Self::from_rows([[x, o, o, o], [o, y, o, o], [o, o, z, o], [o, o, o, l]])
+    }
+
+    pub fn rotation_x(angle: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let (sin, cos) = angle.sin_cos();
+        Self::from_rows([
+            [l, o, o, o],
+            [o, cos, -sin, o],
+            [o, sin, cos, o],
+            [o, o, o, l],
+        ])
+    }
+
+    pub fn rotation_y(angle: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let (sin, cos) = angle.sin_cos();
+        Self::from_rows([
+            [cos, o, sin, o],
+            [o, l, o, o],
+            [-sin, o, cos, o],
+            [o, o, o, l],
+        ])
+    }
+
+    pub fn rotation_z(angle: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let (sin, cos) = angle.sin_cos();
+        Self::from_rows([
+            [cos, -sin, o, o],
+            [sin, cos, o, o],
+            [o, o, l, o],
+            [o, o, o, l],
+        ])
+    }
+
+    /// Rotation by `angle` radians around an arbitrary axis (Rodrigues).
+    pub fn rotation(axis: Vec3<T>, angle: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let Vec3 { x, y, z } = axis.normalize();
+        let (sin, cos) = angle.sin_cos();
+        let t = l - cos;
+        Self::from_rows([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, o],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, o],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, o],
+            [o, o, o, l],
+        ])
+    }
+
+    /// Right-handed orthographic projection mapping the box onto the
+    /// [-1, 1] cube, with the camera looking down -z.
+    pub fn orthographic(left: T, right: T, bottom: T, top: T, near: T, far: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let two = l + l;
+        Self::from_rows([
+            [two / (right - left), o, o, -(right + left) / (right - left)],
+            [o, two / (top - bottom), o, -(top + bottom) / (top - bottom)],
+            [o, o, -two / (far - near), -(far + near) / (far - near)],
+            [o, o, o, l],
+        ])
+    }
+
+    /// Right-handed perspective projection with a vertical field of view in
+    /// radians. Points between `near` and `far` end up with z in [-1, 1]
+    /// after the perspective divide.
+    pub fn perspective(fov_y: T, aspect: T, near: T, far: T) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let two = l + l;
+        let f = l / (fov_y / two).tan();
+        Self::from_rows([
+            [f / aspect, o, o, o],
+            [o, f, o, o],
+            [
+                o,
+                o,
+                (far + near) / (near - far),
+                two * far * near / (near - far),
+            ],
+            [o, o, -l, o],
+        ])
+    }
+
+    /// View matrix for a camera at `eye` looking at `target`.
+    pub fn look_at(eye: Vec3<T>, target: Vec3<T>, up: Vec3<T>) -> Self {
+        let (o, l) = (T::zero(), T::one());
+        let forward = (target - eye).normalize();
+        let side = forward.cross(up).normalize();
+        let up = side.cross(forward);
+        Self::from_rows([
+            [side.x, side.y, side.z, -side.dot(eye)],
+            [up.x, up.y, up.z, -up.dot(eye)],
+            [-forward.x, -forward.y, -forward.z, forward.dot(eye)],
+            [o, o, o, l],
+        ])
+    }
+
+    /// Transforms a point (w = 1) and applies the perspective divide.
+    pub fn transform_point(&self, p: Vec3<T>) -> Vec3<T> {
+        (*self * p.extend(T::one())).perspective_divide()
+    }
+}
+
+impl<T: Float> Mul for Mat3x3<T> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            m: multiply(&self.m, &rhs.m),
+        }
+    }
+}
+
+impl<T: Float> Mul for Mat4x4<T> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            m: multiply(&self.m, &rhs.m),
+        }
+    }
+}
+
+impl<T: Float> Mul<Vec3<T>> for Mat3x3<T> {
+    type Output = Vec3<T>;
+    fn mul(self, v: Vec3<T>) -> Vec3<T> {
+        let row = |r: [T; 3]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vec3::new(row(self.m[0]), row(self.m[1]), row(self.m[2]))
+    }
+}
+
+impl<T: Float> Mul<Vec4<T>> for Mat4x4<T> {
+    type Output = Vec4<T>;
+    fn mul(self, v: Vec4<T>) -> Vec4<T> {
+        let row = |r: [T; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3] * v.w;
+        Vec4::new(
+            row(self.m[0]),
+            row(self.m[1]),
+            row(self.m[2]),
+            row(self.m[3]),
+        )
+    }
+}
+
+impl<T: Float> Mul<T> for Mat3x3<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self {
+        Self {
+            m: self.m.map(|row| row.map(|v| v * rhs)),
+        }
+    }
+}
+
+impl<T: Float> Mul<T> for Mat4x4<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self {
+        Self {
+            m: self.m.map(|row| row.map(|v| v * rhs)),
+        }
+    }
+}
+
+impl<T: Float> Default for Mat3x3<T> {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl<T: Float> Default for Mat4x4<T> {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn close<const N: usize>(a: [[f64; N]; N], b: [[f64; N]; N]) -> bool {
+        a.iter()
+            .flatten()
+            .zip(b.iter().flatten())
+            .all(|(x, y)| (x - y).abs() < 1e-9)
+    }
+
+    #[test]
+    fn affine_2d_transforms() {
+        let m =
+            Mat3x3::translation(1.0, 2.0) * Mat3x3::rotation(FRAC_PI_2) * Mat3x3::scale(2.0, 2.0);
+        let p = m.transform_point(Vec2::new(1.0, 0.0));
+        assert!((p.x - 1.0).abs() < 1e-9 && (p.y - 4.0).abs() < 1e-9);
+        let v = m.transform_vector(Vec2::new(1.0, 0.0));
+        assert!(v.x.abs() < 1e-9 && (v.y - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m = Mat4x4::translation(1.0, -2.0, 3.0)
+            * Mat4x4::rotation(Vec3::new(1.0, 1.0, 0.0), 0.7)
+            * Mat4x4::scale(2.0, 3.0, 4.0);
+        let inv = m.inverse().unwrap();
+        assert!(close((m * inv).m, Mat4x4::identity().m));
+        assert!(close((inv * m).m, Mat4x4::identity().m));
+        assert!((m.determinant() - 24.0).abs() < 1e-9);
+
+        let m3 = Mat3x3::translation(3.0, 4.0) * Mat3x3::rotation(1.2);
+        assert!(close((m3 * m3.inverse().unwrap()).m, Mat3x3::identity().m));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert_eq!(Mat4x4::scale(1.0, 0.0, 1.0).inverse(), None);
+        assert_eq!(Mat3x3::scale(0.0, 1.0).determinant(), 0.0);
+        let dependent = Mat3x3::from_rows([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]);
+        assert_eq!(dependent.inverse(), None);
+    }
+
+    #[test]
+    fn small_scales_invert() {
+        let inv = Mat4x4::scale(1e-4, 1e-4, 1e-4).inverse().unwrap();
+        assert!(close(inv.m, Mat4x4::scale(1e4, 1e4, 1e4).m));
+        let inv = Mat4x4::scale(1e-4f32, 1e-4, 1e-4).inverse().unwrap();
+        assert!((inv.m[0][0] - 1e4).abs() < 1e-2);
+        let inv = Mat3x3::scale(1e-20, 1e-20).inverse().unwrap();
+        assert!((inv.m[0][0] - 1e20).abs() < 1e6 && (inv.m[1][1] - 1e20).abs() < 1e6);
+    }
+
+    #[test]
+    fn nan_entries_do_not_panic() {
+        let m = Mat3x3::from_rows([[f64::NAN, 1.0, 0.0], [1.0, 2.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert!(m.determinant().is_nan());
+        assert_eq!(m.inverse(), None);
+        assert_eq!(Mat3x3::scale(f64::NAN, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let m = Mat3x3::from_rows([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]);
+        assert_eq!(
+            m.transpose().m,
+            [[1.0, 4.0, 7.0], [2.0, 5.0, 8.0], [3.0, 6.0, 9.0]]
+        );
+        assert_eq!(m.transpose().transpose(), m);
+    }
+
+    #[test]
+    fn rotations_match_axis_angle() {
+        let a = 0.4;
+        assert!(close(
+            Mat4x4::rotation_x(a).m,
+            Mat4x4::rotation(Vec3::new(1.0, 0.0, 0.0), a).m
+        ));
+        assert!(close(
+            Mat4x4::rotation_y(a).m,
+            Mat4x4::rotation(Vec3::new(0.0, 1.0, 0.0), a).m
+        ));
+        assert!(close(
+            Mat4x4::rotation_z(a).m,
+            Mat4x4::rotation(Vec3::new(0.0, 0.0, 1.0), a).m
+        ));
+    }
+
+    #[test]
+    fn projections_map_frustum_to_unit_cube() {
+        let ortho = Mat4x4::orthographic(-2.0, 2.0, -1.0, 1.0, 0.1, 10.0);
+        let p = ortho.transform_point(Vec3::new(2.0, -1.0, -10.0));
+        assert!((p.x - 1.0).abs() < 1e-9 && (p.y + 1.0).abs() < 1e-9 && (p.z - 1.0).abs() < 1e-9);
+
+        let persp = Mat4x4::perspective(FRAC_PI_2, 1.0, 1.0, 100.0);
+        let near = persp.transform_point(Vec3::new(1.0, 1.0, -1.0));
+        let far = persp.transform_point(Vec3::new(0.0, 0.0, -100.0));
+        assert!((near.x - 1.0).abs() < 1e-9 && (near.y - 1.0).abs() < 1e-9);
+        assert!((near.z + 1.0).abs() < 1e-9 && (far.z - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn look_at_moves_target_onto_negative_z() {
+        let view = Mat4x4::look_at(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let p = view.transform_point(Vec3::new(0.0, 0.0, 0.0));
+        assert!(p.x.abs() < 1e-9 && p.y.abs() < 1e-9 && (p.z + 5.0).abs() < 1e-9);
+    }
+}
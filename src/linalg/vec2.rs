@@ -0,0 +1,80 @@
+#![allow(unused)]
+
+use num::Float;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+impl_vector!(Vec2 { x, y });
+
+impl<T: Float> Vec2<T> {
+    /// The z component of the 3D cross product, i.e. the signed area of the
+    /// parallelogram spanned by both vectors.
+    pub fn cross(self, other: Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// The vector rotated a quarter turn counter-clockwise.
+    pub fn perp(self) -> Self {
+        Self::new(-self.y, self.x)
+    }
+
+    pub fn rotate(self, angle: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+}
+
+impl<T> From<(T, T)> for Vec2<T> {
+    fn from((x, y): (T, T)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T> From<Vec2<T>> for (T, T) {
+    fn from(v: Vec2<T>) -> Self {
+        (v.x, v.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = Vec2::new(1.0, 2.0);
+        let b = Vec2::new(3.0, -1.0);
+        assert_eq!(a + b, Vec2::new(4.0, 1.0));
+        assert_eq!(a - b, Vec2::new(-2.0, 3.0));
+        assert_eq!(a * 2.0, Vec2::new(2.0, 4.0));
+        assert_eq!(a * b, Vec2::new(3.0, -2.0));
+        assert_eq!(-a / 2.0, Vec2::new(-0.5, -1.0));
+        let mut c = a;
+        c += b;
+        c *= 3.0;
+        assert_eq!(c, Vec2::new(12.0, 3.0));
+    }
+
+    #[test]
+    fn products() {
+        let x = Vec2::new(1.0, 0.0);
+        let y = Vec2::new(0.0, 1.0);
+        assert_eq!(x.dot(y), 0.0);
+        assert_eq!(x.cross(y), 1.0);
+        assert_eq!(y.cross(x), -1.0);
+        assert_eq!(x.perp(), y);
+    }
+
+    #[test]
+    fn length_and_lerp() {
+        let v = Vec2::new(3.0f32, 4.0);
+        assert_eq!(v.length(), 5.0);
+        assert!((v.normalize().length() - 1.0).abs() < 1e-6);
+        assert_eq!(Vec2::<f32>::zero().normalize(), Vec2::zero());
+        assert_eq!(Vec2::zero().lerp(v, 0.5), Vec2::new(1.5, 2.0));
+    }
+}
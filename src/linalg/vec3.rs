@@ -0,0 +1,63 @@
+#![allow(unused)]
+
+use num::Float;
+
+use super::vec2::Vec2;
+use super::vec4::Vec4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl_vector!(Vec3 { x, y, z });
+
+impl<T: Float> Vec3<T> {
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn xy(self) -> Vec2<T> {
+        Vec2::new(self.x, self.y)
+    }
+
+    pub fn extend(self, w: T) -> Vec4<T> {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+}
+
+impl<T> From<(T, T, T)> for Vec3<T> {
+    fn from((x, y, z): (T, T, T)) -> Self {
+        Self { x, y, z }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_follows_right_hand_rule() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        assert_eq!(x.cross(y), z);
+        assert_eq!(y.cross(z), x);
+        assert_eq!(y.cross(x), -z);
+    }
+
+    #[test]
+    fn dot_and_normalize() {
+        let v = Vec3::new(2.0f64, 3.0, 6.0);
+        assert_eq!(v.dot(v), 49.0);
+        assert_eq!(v.length(), 7.0);
+        assert!((v.normalize().length() - 1.0).abs() < 1e-12);
+        assert_eq!(v.extend(1.0), Vec4::new(2.0, 3.0, 6.0, 1.0));
+    }
+}
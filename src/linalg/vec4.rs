@@ -0,0 +1,52 @@
+#![allow(unused)]
+
+use num::Float;
+
+use super::vec3::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec4<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+    pub w: T,
+}
+
+impl_vector!(Vec4 { x, y, z, w });
+
+impl<T: Float> Vec4<T> {
+    pub fn xyz(self) -> Vec3<T> {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Divides by `w`, turning homogeneous clip coordinates into a point.
+    pub fn perspective_divide(self) -> Vec3<T> {
+        self.xyz() / self.w
+    }
+}
+
+impl<T> From<(T, T, T, T)> for Vec4<T> {
+    fn from((x, y, z, w): (T, T, T, T)) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        let a = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(a + a, a * 2.0);
+        assert_eq!(a - a, Vec4::zero());
+        assert_eq!(a.dot(Vec4::splat(1.0)), 10.0);
+        assert_eq!(a.lerp(Vec4::zero(), 1.0), Vec4::zero());
+    }
+
+    #[test]
+    fn perspective_divide() {
+        let v = Vec4::new(2.0, 4.0, 6.0, 2.0);
+        assert_eq!(v.perspective_divide(), Vec3::new(1.0, 2.0, 3.0));
+    }
+}
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
 
+#[allow(dead_code)]
 const SKY_COLOR: Color = Color::rgb(135.0 / 255.0, 206.0 / 255.0, 250.0 / 255.0);
+#[allow(dead_code)]
 const MOVE_SPEED: f32 = 100.0;
 const FALL_SPEED: f32 = 220.0;
 
@@ -191,6 +193,7 @@ fn check_horizontal_overlap(
     player_right > block_left && player_left < block_right
 }
 
+#[allow(clippy::type_complexity)]
 fn character_movement(
     mut commands: Commands,
     mut player: Query<(
@@ -281,8 +284,6 @@ fn ground_detection(
     *last = *pos;
 }
 
-use std::cmp::max;
-
 fn coin_pickup(
     mut commands: Commands,
     coin_query: Query<(Entity, &Transform), With<Coin>>,
@@ -307,29 +308,136 @@ fn coin_pickup(
 
 use std::env;
 
+mod animation;
+mod blend;
 mod buffer;
+mod camera;
 mod canvas;
+mod clip;
+mod clock;
 mod colors;
+mod display;
+mod export;
+mod font;
+mod grid;
+mod level;
+mod linalg;
+mod mesh;
+mod pipeline;
+mod postprocess;
+mod raster;
+mod replay;
+mod shapes;
+mod sprite;
+mod stroke;
+mod theme;
+mod tilemap;
+mod tiles;
+mod world;
 
 use canvas::Canvas;
-use colors::Colors;
+
+/// The value following `--name` on the command line, if given.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == name)?;
+    let value = args.get(index + 1).unwrap_or_else(|| panic!("{name} needs a value"));
+    Some(value)
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let query = &args[1];
 
     if query == "ty" {
-        // use linalg::vec2::Vec2;
-        // use linalg::{matrix::Mat4x4, vec4::Vec4};
-        use minifb::{Key, Window, WindowOptions};
-        use rand::Rng;
+        use minifb::{Key, KeyRepeat, Window, WindowOptions};
+        use animation::{Animation, Clip, PlayMode};
+        use camera::Camera2D;
+        use clock::FixedTimestep;
+        use display::DisplayList;
+        use font::{Align, TextStyle};
+        use level::Level;
+        use postprocess::{Effect, Lut3D, PostProcess, Upscale};
+        use replay::Replay;
+        use sprite::{Blit, Sprite};
+        use theme::Theme;
+        use std::rc::Rc;
+        use world::{Events, GridWorld, Input};
 
         // use mki::{bind_key, Action, InhibitEvent, Keyboard, Sequence};
 
         const WIDTH: usize = 640;
         const HEIGHT: usize = 360;
 
+        // Physics ticks and drawn frames per second; an fps of 0 draws as
+        // fast as possible
+        let rate = |name, default: f64| {
+            flag_value(&args, name).map_or(default, |v| {
+                v.parse()
+                    .ok()
+                    .filter(|rate: &f64| rate.is_finite() && *rate >= 0.0)
+                    .unwrap_or_else(|| panic!("{name} needs a number, got {v:?}"))
+            })
+        };
+        let period = |name, rate| clock::period(rate).unwrap_or_else(|| panic!("{name} of {rate} is out of range"));
+        let tick_rate = rate("--tick-rate", 10.0);
+        let fps = rate("--fps", 60.0);
+        assert!(tick_rate > 0.0, "--tick-rate must be positive");
+        let tick = period("--tick-rate", tick_rate);
+        let frame_time = (fps > 0.0).then(|| period("--fps", fps));
+
         let mut canvas = Canvas::new(WIDTH, HEIGHT);
+        canvas.set_camera(Some(Camera2D::new(2.0, 2.0)));
+        let mut theme = match flag_value(&args, "--theme") {
+            Some(name) => Theme::by_name(name).unwrap_or_else(|| {
+                let names: Vec<_> = Theme::ALL.iter().map(|t| t.name).collect();
+                panic!("unknown theme {name:?}, expected one of {}", names.join(", "))
+            }),
+            None => Theme::CLASSIC,
+        };
+        let base_sheet = Sprite::load("assets/player-sheet.png").unwrap();
+        let recolor = |theme: &Theme| base_sheet.swap_colors(&[(Theme::CLASSIC.player, theme.player)]);
+        let mut player_sheet = Rc::new(recolor(&theme));
+
+        // The sheet is a single row of 16x16 frames
+        let frames = |indices: &[usize], duration: f32, mode: PlayMode| {
+            Clip::from_sheet(base_sheet.bounds(), (16, 16), indices.iter().copied(), duration, mode)
+        };
+        let mut player_animation = Animation::new();
+        player_animation.add_clip("idle", frames(&[0, 1], 0.5, PlayMode::Loop));
+        player_animation.add_clip("run", frames(&[2, 3, 4, 5], 0.1, PlayMode::Loop));
+        player_animation.add_clip("jump", frames(&[6], 0.1, PlayMode::Once));
+        player_animation.add_clip("fall", frames(&[7], 0.1, PlayMode::Once));
+        player_animation.add_clip("death", frames(&[8, 9], 0.3, PlayMode::Once).with_durations(&[0.3, 0.7]));
+        let mut facing_left = false;
+
+        // Effects start switched off; F1 to F7 toggle them in this order
+        let mut post = PostProcess::new();
+        let warm = Lut3D::from_fn(17, |(r, g, b)| (r * 1.1 + 0.03, g * 1.02, b * 0.85));
+        for effect in [
+            Effect::Scanlines { intensity: 0.35 },
+            Effect::Vignette { strength: 0.6 },
+            Effect::GaussianBlur { sigma: 1.5 },
+            Effect::Bloom {
+                threshold: 0.6,
+                sigma: 4.0,
+                intensity: 0.8,
+            },
+            Effect::ColorGrade(warm),
+            Effect::Upscale(Upscale::Scale2x),
+            Effect::Upscale(Upscale::Hq2x),
+        ] {
+            let index = post.push(effect);
+            post.set_enabled(index, false);
+        }
+        let effect_keys = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7];
+        let mut last_frame = std::time::Instant::now();
+
+        // Each frame is recorded and compared with the one on screen, so only
+        // changed areas are drawn again. The terrain is recorded only when
+        // the collision grid or the theme changes.
+        let mut shown: Option<DisplayList> = None;
+        let mut terrain = DisplayList::new();
+        let mut terrain_source = None;
 
         let mut window = Window::new(
             "Test - ESC to exit",
@@ -344,218 +452,261 @@ fn main() {
             panic!("{}", e);
         });
 
-        // Limit to max ~60 fps update rate
-        window.limit_update_rate(Some(std::time::Duration::from_millis(100)));
-
-        let mut player_array_pos: (isize, isize) = (10, 10);
-
-        let mut x_pos = 1;
-        let mut forward = true;
-
-        let mut level = 1;
-
-        let mut right_velo: isize = 0;
-        let mut up_velo: isize = 0;
-
-        let mut victory_pos: (isize, isize) = (17, 17);
-
-        let mut collisions = vec![vec![false; 19]; 19];
-
-        for n in 0..18 {
-            collisions[n][1] = true
-        }
+        window.limit_update_rate(frame_time);
+
+        let levels = Level::load_dir("levels").unwrap_or_else(|e| panic!("levels: {}", e));
+        assert!(!levels.is_empty(), "no level files in levels/");
+
+        // A replay given with --replay drives the game instead of the keys
+        // until it runs out or the game stops matching it. --record saves
+        // the ticks played to a file on exit.
+        let mut playback = flag_value(&args, "--replay")
+            .map(|path| (Replay::load(path).unwrap_or_else(|e| panic!("replay: {}", e)), 0));
+        let start = match &playback {
+            Some((replay, _)) => replay.level,
+            None => flag_value(&args, "--level").map_or(0, |v| match v.parse::<usize>() {
+                Ok(level @ 1..) => level - 1,
+                _ => panic!("--level needs a number from 1, got {v:?}"),
+            }),
+        };
+        assert!(start < levels.len(), "there are only {} levels", levels.len());
+        let seed = match (&playback, flag_value(&args, "--seed")) {
+            (Some((replay, _)), _) => replay.seed,
+            (None, Some(v)) => v.parse().unwrap_or_else(|_| panic!("--seed needs a number, got {v:?}")),
+            (None, None) => std::time::UNIX_EPOCH.elapsed().map_or(0, |time| time.as_nanos() as u64),
+        };
+        let mut recording = flag_value(&args, "--record").map(|path| (path, Replay::new(start, seed)));
+        let mut world = GridWorld::starting_at(levels, start);
+        // The game advances in fixed ticks however fast frames are drawn.
+        // Keys held during any frame since the last tick count for the next.
+        let mut clock = FixedTimestep::new(tick);
+        let mut input = Input::default();
+        let mut previous_player = world.player();
+
+        // Message shown in the window and the seconds it stays up
+        let mut message = (String::new(), 0.0);
 
         while window.is_open() && !window.is_key_down(Key::Escape) {
-            canvas.clear();
-            player_array_pos.1 += up_velo;
-
-            if player_array_pos.0 == 18 || player_array_pos.0 == 0 || player_array_pos.1 == 0 {
-                println!("dead");
-                if level == 1 {
-                    player_array_pos = (10, 10);
-                    right_velo = 0;
-                    up_velo = 0;
-                } else if level == 2 {
-                    player_array_pos = (2, 5);
-                    right_velo = 0;
-                    up_velo = 0;
-                } else if level == 3 {
-                    player_array_pos = (17, 5);
-                    right_velo = 0;
-                    up_velo = 0;
-                    x_pos = 1;
-                    forward = true
+            for (index, &key) in effect_keys.iter().enumerate() {
+                if window.is_key_pressed(key, KeyRepeat::No) {
+                    let state = if post.toggle(index) { "on" } else { "off" };
+                    message = (format!("{} {state}", post.passes()[index].effect.name()), 1.0);
                 }
             }
 
-            if player_array_pos.0 == 2 {
-                up_velo = 0;
+            // Upscaling effects enlarge the frame, so draw it smaller
+            let (width, height) = window.get_size();
+            let (width, height) = (width / post.scale(), height / post.scale());
+            if width > 0 && height > 0 && (width, height) != canvas.size() {
+                canvas.resize(width, height);
+                shown = None;
             }
-
-            if window.is_key_down(Key::A) {
-                right_velo = -1;
-            } else if window.is_key_down(Key::D) {
-                right_velo = 1;
-            } else {
-                right_velo = 0;
+            if window.is_key_pressed(Key::T, KeyRepeat::No) {
+                theme = theme.next();
+                player_sheet = Rc::new(recolor(&theme));
+                message = (theme.name.to_owned(), 1.0);
             }
 
-            if collisions[player_array_pos.0 as usize][(player_array_pos.1 - 1) as usize] == false {
-                up_velo = max(-1, up_velo - 1);
-            } else {
-                if level == 3 && (player_array_pos.0 == 2 || player_array_pos.0 == 3) {
-                    up_velo = 1;
-                } else {
-                    up_velo = 0;
+            let now = std::time::Instant::now();
+            let delta = now - last_frame;
+            player_animation.update(delta.as_secs_f32());
+            last_frame = now;
+
+            let mut frame = DisplayList::new();
+            frame.set_color(theme.background);
+            frame.fill();
+            input.left |= window.is_key_down(Key::A);
+            input.right |= window.is_key_down(Key::D);
+            input.jump |= window.is_key_down(Key::W);
+            let mut events = Events::default();
+            for _ in 0..clock.advance(delta) {
+                let recorded = playback.as_ref().and_then(|(replay, next)| replay.ticks.get(*next).copied());
+                let tick_input = recorded.map_or(input, |(input, _)| input);
+                previous_player = world.player();
+                let step = world.step(tick_input);
+                input = Input::default();
+                if let Some((_, expected)) = recorded {
+                    let (replay, next) = playback.as_mut().unwrap();
+                    if step != expected {
+                        eprintln!("replay diverged at tick {next}: expected {expected:?}, got {step:?}");
+                        message = ("replay diverged".to_owned(), 2.0);
+                        playback = None;
+                    } else if *next + 1 == replay.ticks.len() {
+                        println!("replay matched for all {} ticks", replay.ticks.len());
+                        message = ("replay over".to_owned(), 2.0);
+                        playback = None;
+                    } else {
+                        *next += 1;
+                    }
+                }
+                if let Some((_, replay)) = &mut recording {
+                    replay.record(tick_input, step);
+                }
+                // Respawns and new levels jump rather than slide
+                if step.died || step.won {
+                    previous_player = world.player();
                 }
+                events.died |= step.died;
+                events.won |= step.won;
+                events.level_changed |= step.level_changed;
             }
-
-            if collisions[player_array_pos.0 as usize][(player_array_pos.1) as usize] == true {
-                up_velo = 0;
-                player_array_pos.1 += 2;
+            if events.died {
+                message = ("dead".to_owned(), 1.0);
             }
-
-            if collisions[player_array_pos.0 as usize][(player_array_pos.1 - 1) as usize] == true
-                && window.is_key_down(Key::W)
-            {
-                up_velo = 3;
+            if events.won {
+                message = ("victory".to_owned(), 1.0);
             }
 
-            if collisions[(player_array_pos.0 - 1) as usize][player_array_pos.1 as usize] == false
-                && right_velo < 0
-            {
-                player_array_pos.0 -= 1;
+            let (player, collisions) = (world.player(), world.collisions());
+            let (columns, rows) = (collisions.width() as f32, collisions.height() as f32);
+            // The game is drawn in cells, scaled so the larger side of the
+            // grid plus one spare cell spans the view and centered on it
+            let cell = 2.0 / (columns.max(rows) + 1.0);
+            frame.push_transform();
+            frame.scale(cell, cell);
+            frame.translate(-(columns + 1.0) / 2.0, -(rows + 1.0) / 2.0);
+
+            // Terrain is drawn from the collision grid itself, one tile per
+            // cell
+            if terrain_source.as_ref() != Some(&(collisions.clone(), theme)) {
+                terrain = DisplayList::new();
+                terrain.draw_tilemap(&theme.terrain_tiles(collisions));
+                terrain_source = Some((collisions.clone(), theme));
             }
+            frame.append(&terrain);
 
-            if collisions[(player_array_pos.0 + 1) as usize][player_array_pos.1 as usize] == false
-                && right_velo > 0
-            {
-                player_array_pos.0 += 1;
+            frame.push_transform();
+            let alpha = clock.alpha();
+            frame.translate(
+                previous_player.0 as f32 + (player.0 - previous_player.0) as f32 * alpha,
+                previous_player.1 as f32 + (player.1 - previous_player.1) as f32 * alpha,
+            );
+            if world.direction() != 0 {
+                facing_left = world.direction() < 0;
             }
-
-            if player_array_pos.0 == victory_pos.0 && 19 - victory_pos.1 == player_array_pos.1 {
-                println!("victory");
-                level += 1;
-                if level == 1 {
-                    player_array_pos = (10, 10);
-                    right_velo = 0;
-                    up_velo = 0;
-                } else if level == 2 {
-                    player_array_pos = (2, 5);
-                    right_velo = 0;
-                    up_velo = 0;
-                    victory_pos = (14, 5);
-                    collisions = vec![vec![false; 19]; 19];
-                    collisions[2][0] = true;
-                    collisions[3][0] = true;
-                    collisions[2][1] = true;
-                    collisions[3][1] = true;
-                    for n in 0..6 {
-                        collisions[6][n] = true;
-                        collisions[7][n] = true;
-                    }
-                    for n in 0..10 {
-                        collisions[10][n] = true;
-                        collisions[11][n] = true;
-                    }
-                    for n in 0..14 {
-                        collisions[14][n] = true;
-                        collisions[15][n] = true;
-                    }
-                } else if level == 3 {
-                    player_array_pos = (17, 5);
-                    right_velo = 0;
-                    up_velo = 0;
-                    victory_pos = (15, 5);
-                    collisions = vec![vec![false; 19]; 19];
-                }
+            let dying = player_animation.current() == "death" && !player_animation.is_finished();
+            if events.died {
+                player_animation.play("death");
+            } else if !dying {
+                player_animation.play(match (world.up_velocity(), world.direction()) {
+                    (1.., _) => "jump",
+                    (..=-1, _) => "fall",
+                    (0, 0) => "idle",
+                    _ => "run",
+                });
             }
-
-            if level == 3 {
-                collisions = vec![vec![false; 19]; 19];
-                collisions[18 - x_pos][1] = true;
-                collisions[17 - x_pos][1] = true;
-                collisions[2][14 - x_pos] = true;
-                collisions[3][14 - x_pos] = true;
-                collisions[10][10] = true;
-                collisions[11][10] = true;
+            let blit = Blit {
+                flip_x: facing_left,
+                ..Default::default()
+            };
+            frame.draw_animation(&player_sheet, &player_animation, (0.0, 1.0), (1.0, 0.0), &blit);
+            frame.pop_transform();
+
+            // The flag floats just above the goal cell
+            if let Some(current) = world.level() {
+                frame.set_color(theme.goal);
+                frame.push_transform();
+                frame.translate(current.goal.0 as f32, (current.goal.1 + 1) as f32);
+                frame.fill_tri((1.5, 0.3), (1.0, 0.9), (0.5, 0.3));
+                frame.pop_transform();
             }
 
-            let player_pos = (
-                (player_array_pos.0 as f32 - 10.0) * 0.1,
-                (player_array_pos.1 as f32 - 10.0) * 0.1,
-            );
-            canvas.set_color(Colors::WHITE);
+            frame.set_color(theme.hazard);
+            frame.line((0.0, 0.0), (0.0, rows + 1.0));
+            frame.line((columns, 0.0), (columns, rows + 1.0));
+            frame.pop_transform();
 
-            if level == 1 {
-                canvas.line((-1.0, -0.8), (1.0, -0.8));
+            if world.is_finished() {
+                message = ("ultimate mega winner".to_owned(), 1.0);
             }
 
-            if level == 2 {
-                canvas.sqr((-0.8, -1.0), (-0.6, -1.0), (-0.6, -0.8), (-0.8, -0.8));
-                canvas.sqr((-0.4, -1.0), (-0.2, -1.0), (-0.2, -0.4), (-0.4, -0.4));
-                canvas.sqr((-0.0, -1.0), (0.2, -1.0), (0.2, -0.0), (-0.0, -0.0));
-                canvas.sqr((0.4, -1.0), (0.6, -1.0), (0.6, 0.4), (0.4, 0.4));
+            frame.set_color(theme.text);
+            frame.set_text_style(TextStyle {
+                scale: 2,
+                ..Default::default()
+            });
+            frame.text((-0.95, 0.95), &format!("level {}", (world.level_index() + 1).min(world.levels().len())));
+
+            if message.1 > 0.0 {
+                frame.set_text_style(TextStyle {
+                    scale: 3,
+                    align: Align::Center,
+                    color: Some(theme.highlight),
+                });
+                frame.text((0.0, 0.5), &message.0);
+                message.1 -= delta.as_secs_f32();
             }
 
-            if level == 3 {
-                canvas.sqr(
-                    (0.7 - (x_pos as f32 / 10.0), -0.8),
-                    (0.9 - (x_pos as f32 / 10.0), -0.8),
-                    (0.9 - (x_pos as f32 / 10.0), -0.9),
-                    (0.7 - (x_pos as f32 / 10.0), -0.9),
-                );
-                canvas.sqr(
-                    (-0.8, 0.4 - (x_pos as f32 / 10.0)),
-                    (-0.6, 0.4 - (x_pos as f32 / 10.0)),
-                    (-0.6, 0.3 - (x_pos as f32 / 10.0)),
-                    (-0.8, 0.3 - (x_pos as f32 / 10.0)),
-                );
-                canvas.sqr((0.0, 0.0), (0.2, 0.0), (0.2, 0.1), (0.0, 0.1));
+            match &shown {
+                Some(shown) => {
+                    let dirty = canvas.diff(shown, &frame);
+                    canvas.redraw(&frame, &dirty);
+                }
+                None => canvas.replay(&frame),
             }
+            shown = Some(frame);
 
-            canvas.sqr(
-                (0.1 + player_pos.0, 0.1 + player_pos.1),
-                (0.1 + player_pos.0, 0.0 + player_pos.1),
-                (0.0 + player_pos.0, 0.0 + player_pos.1),
-                (0.0 + player_pos.0, 0.1 + player_pos.1),
-            );
+            let output = post.apply(canvas.as_buffer());
+            let (width, height) = output.size();
+            window
+                .update_with_buffer(&output, width, height)
+                .unwrap();
+        }
 
-            canvas.set_color(Colors::BLUE);
-            canvas.tri(
-                (
-                    0.15 + (victory_pos.0 - 10) as f32 / 10.0,
-                    0.03 + (victory_pos.1 - 10) as f32 / -10.0,
-                ),
-                (
-                    0.1 + (victory_pos.0 - 10) as f32 / 10.0,
-                    0.09 + (victory_pos.1 - 10) as f32 / -10.0,
-                ),
-                (
-                    0.05 + (victory_pos.0 - 10) as f32 / 10.0,
-                    0.03 + (victory_pos.1 - 10) as f32 / -10.0,
-                ),
-            );
+        if let Some((path, replay)) = recording {
+            replay.save(path).unwrap_or_else(|e| panic!("{path}: {}", e));
+            println!("recorded {} ticks to {path}", replay.ticks.len());
+        }
+    }
 
-            canvas.set_color(Colors::RED);
-            canvas.line((-1.0, -1.0), (-1.0, 1.0));
-            canvas.line((0.9, -1.0), (0.9, 1.0));
+    if query == "3d" {
+        use colors::Color;
+        use linalg::matrix::Mat4x4;
+        use mesh::Mesh;
+        use minifb::{Key, KeyRepeat, Window, WindowOptions};
+        use pipeline::Pipeline;
+        use sprite::Sprite;
+        use tiles::TileRenderer;
 
-            if forward {
-                x_pos += 1;
-            } else {
-                x_pos -= 1;
-            }
+        const WIDTH: usize = 640;
+        const HEIGHT: usize = 360;
 
-            if x_pos == 12 {
-                forward = !forward;
-            } else if x_pos == 1 {
-                forward = true;
+        let mesh = match args.get(2) {
+            Some(path) => Mesh::load_obj(path).unwrap_or_else(|e| panic!("{}", e)),
+            None => Mesh::cube(1.0),
+        };
+
+        let texture = Sprite::load("assets/brick.png").unwrap();
+
+        let mut canvas = Canvas::new(WIDTH, HEIGHT);
+        canvas.enable_depth();
+        let mut pipeline = Pipeline::new(WIDTH as f32 / HEIGHT as f32);
+
+        let mut window = Window::new("3D - ESC to exit", WIDTH, HEIGHT, WindowOptions::default())
+            .unwrap_or_else(|e| {
+                panic!("{}", e);
+            });
+        window.limit_update_rate(Some(std::time::Duration::from_millis(16)));
+
+        let mut tiles = TileRenderer::new();
+        let mut angle: f32 = 0.0;
+        let mut wireframe = false;
+        while window.is_open() && !window.is_key_down(Key::Escape) {
+            if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+                wireframe = !wireframe;
             }
 
-            if (level == 4) {
-                println!("ultimate mega winner");
+            canvas.clear();
+            canvas.set_color(Color::GREEN);
+            pipeline.model = Mat4x4::rotation_y(angle) * Mat4x4::rotation_x(angle * 0.5);
+            if wireframe {
+                canvas.wireframe(&pipeline, &mesh);
+            } else {
+                tiles.clear();
+                pipeline.record_solid(&mut tiles, canvas.as_buffer(), &mesh, Some(&texture));
+                canvas.render_tiles(&tiles);
             }
+            angle += 0.02;
 
             window
                 .update_with_buffer(canvas.buffer(), WIDTH, HEIGHT)
@@ -563,6 +714,144 @@ fn main() {
         }
     }
 
+    if query == "bench" {
+        use buffer::Buffer;
+        use colors::Color;
+        use linalg::{vec2::Vec2, vec3::Vec3};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use raster::ScreenVertex;
+        use std::time::{Duration, Instant};
+        use tiles::TileRenderer;
+
+        let size = |name, default| {
+            flag_value(&args, name).map_or(default, |v| {
+                v.parse().unwrap_or_else(|_| panic!("{name} needs a number, got {v:?}"))
+            })
+        };
+        let (width, height) = (size("--width", 1920), size("--height", 1080));
+        let frames = size("--frames", 20);
+        let shapes = size("--shapes", 2000);
+        let available = size("--threads", TileRenderer::new().threads);
+        for (name, value) in [("--width", width), ("--height", height), ("--frames", frames), ("--threads", available)] {
+            assert!(value > 0, "{name} must be positive");
+        }
+
+        // The same random scene every run: translucent polygons over
+        // depth-tested triangles
+        let mut rng = StdRng::seed_from_u64(1);
+        let point = |rng: &mut StdRng| (rng.gen_range(0.0..width as f32), rng.gen_range(0.0..height as f32));
+        let triangles: Vec<[ScreenVertex; 3]> = (0..shapes)
+            .map(|_| {
+                let center = point(&mut rng);
+                let depth = rng.gen_range(0.0..1.0);
+                let color = Vec3::new(rng.gen(), rng.gen(), rng.gen());
+                [(); 3].map(|_| ScreenVertex {
+                    position: Vec2::new(center.0 + rng.gen_range(-150.0..150.0), center.1 + rng.gen_range(-150.0..150.0)),
+                    depth,
+                    inv_w: 1.0,
+                    color,
+                    uv: Vec2::new(0.0, 0.0),
+                })
+            })
+            .collect();
+        let polygons: Vec<(Color, Vec<(f32, f32)>)> = (0..shapes)
+            .map(|_| {
+                let color = Color::random().with_alpha(rng.gen_range(32..224));
+                let (x, y) = point(&mut rng);
+                let (w, h) = (rng.gen_range(10.0..200.0), rng.gen_range(10.0..200.0));
+                (color, vec![(x, y), (x + w, y + h * 0.2), (x + w * 0.8, y + h), (x - w * 0.1, y + h * 0.7)])
+            })
+            .collect();
+
+        /// Average time per frame of clearing and drawing, and the last frame.
+        fn time(size: (usize, usize), frames: usize, mut draw: impl FnMut(&mut Buffer)) -> (Duration, Buffer) {
+            let mut buffer = Buffer::new(size.0, size.1);
+            buffer.enable_depth();
+            let start = Instant::now();
+            for _ in 0..frames {
+                buffer.clear();
+                draw(&mut buffer);
+            }
+            (start.elapsed().div_f64(frames as f64), buffer)
+        }
+        let direct = |buffer: &mut Buffer| {
+            buffer.set_color(Color::WHITE);
+            for &triangle in &triangles {
+                buffer.raster_tri(triangle, None);
+            }
+            for (color, points) in &polygons {
+                buffer.set_color(*color);
+                buffer.fill_polygon_f32(points);
+            }
+        };
+        let record = || {
+            let mut tiles = TileRenderer::new();
+            for &triangle in &triangles {
+                tiles.raster_tri(triangle, None);
+            }
+            for (color, points) in &polygons {
+                tiles.set_color(*color);
+                tiles.fill_polygon_f32(points);
+            }
+            tiles
+        };
+
+        println!("{width}x{height}, {shapes} triangles and {shapes} polygons, {frames} frames");
+        let (clear, _) = time((width, height), frames, |_| {});
+        println!("{:<24}{:>10.2?}", "clear", clear);
+        // Timed on its own, without the clear each frame of `time` starts with
+        let fill = {
+            let mut buffer = Buffer::new(width, height);
+            buffer.set_color(Color::RED.with_alpha(128));
+            let start = Instant::now();
+            for _ in 0..frames {
+                buffer.fill();
+            }
+            start.elapsed().div_f64(frames as f64)
+        };
+        println!("{:<24}{:>10.2?}", "translucent fill", fill);
+        let (single, reference) = time((width, height), frames, direct);
+        println!("{:<24}{:>10.2?}", "direct", single);
+        let mut counts = vec![1, 2, 4, available];
+        counts.retain(|&n| n <= available);
+        counts.dedup();
+        for threads in counts {
+            let mut tiles = record();
+            tiles.threads = threads;
+            let (elapsed, result) = time((width, height), frames, |buffer| tiles.render(buffer));
+            let differing = result.iter().zip(reference.iter()).filter(|(a, b)| a != b).count();
+            let label = format!("tiled, {threads} thread{}", if threads == 1 { "" } else { "s" });
+            println!(
+                "{label:<24}{elapsed:>10.2?}  {:.2}x, {differing} pixels differ",
+                single.as_secs_f64() / elapsed.as_secs_f64()
+            );
+        }
+    }
+
+    // Plays a ty replay without a window, for checking recorded runs still
+    // play out the same
+    if query == "replay" {
+        use level::Level;
+        use replay::Replay;
+
+        let path = args.get(2).expect("usage: replay <file>");
+        let replay = Replay::load(path).unwrap_or_else(|e| panic!("{}", e));
+        let levels = Level::load_dir("levels").unwrap_or_else(|e| panic!("levels: {}", e));
+        match replay.play(levels) {
+            Ok(world) => {
+                let state = match world.level() {
+                    Some(_) => format!("on level {}", world.level_index() + 1),
+                    None => "finished the game".to_owned(),
+                };
+                println!("{path}: all {} ticks match, {state}", replay.ticks.len());
+            }
+            Err((tick, message)) => {
+                eprintln!("{path}: tick {tick}: {message}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     if query == "trevor" {
         App::new()
             .add_plugins(
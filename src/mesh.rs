@@ -0,0 +1,235 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::linalg::{vec2::Vec2, vec3::Vec3};
+
+/// An indexed triangle mesh. Triangles wind counter-clockwise when seen
+/// from the front. `uvs` and `colors` are either empty or hold one entry
+/// per position.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3<f32>>,
+    pub uvs: Vec<Vec2<f32>>,
+    pub colors: Vec<Vec3<f32>>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3<f32>>, triangles: Vec<[usize; 3]>) -> Self {
+        Self {
+            positions,
+            triangles,
+            ..Default::default()
+        }
+    }
+
+    pub fn uv(&self, index: usize) -> Vec2<f32> {
+        self.uvs.get(index).copied().unwrap_or_default()
+    }
+
+    /// The vertex color, white when the mesh has none.
+    pub fn color(&self, index: usize) -> Vec3<f32> {
+        self.colors.get(index).copied().unwrap_or(Vec3::splat(1.0))
+    }
+
+    /// An axis-aligned cube with the given edge length, centered on the
+    /// origin. Every face maps the whole texture.
+    pub fn cube(size: f32) -> Self {
+        let h = size / 2.0;
+        let corners = [
+            (-h, -h, -h),
+            (h, -h, -h),
+            (h, h, -h),
+            (-h, h, -h),
+            (-h, -h, h),
+            (h, -h, h),
+            (h, h, h),
+            (-h, h, h),
+        ]
+        .map(Vec3::from);
+        // Counter-clockwise from outside, starting at the face's bottom left
+        let faces = [
+            [4, 5, 6, 7],
+            [1, 0, 3, 2],
+            [0, 4, 7, 3],
+            [5, 1, 2, 6],
+            [7, 6, 2, 3],
+            [0, 1, 5, 4],
+        ];
+        let uvs = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(Vec2::from);
+        let mut mesh = Mesh::default();
+        for face in faces {
+            let base = mesh.positions.len();
+            mesh.positions.extend(face.map(|i| corners[i]));
+            mesh.uvs.extend(uvs);
+            mesh.triangles.push([base, base + 1, base + 2]);
+            mesh.triangles.push([base, base + 2, base + 3]);
+        }
+        mesh
+    }
+
+    pub fn load_obj(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse_obj(&fs::read_to_string(path)?).map_err(|(line, message)| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{line}: {message}", path.display()),
+            )
+        })
+    }
+
+    /// Parses the vertex (`v`, optionally followed by an RGB color), texture
+    /// coordinate (`vt`) and face (`f`) statements of a Wavefront OBJ file,
+    /// triangulating polygons as fans. If only some vertices have a color,
+    /// the others are white. Other statements are ignored. Errors carry the
+    /// 1-based line number.
+    pub fn parse_obj(source: &str) -> Result<Self, (usize, String)> {
+        let mut positions = Vec::new();
+        // One slot per position, so colors stay with their vertex
+        let mut colors: Vec<Option<Vec3<f32>>> = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut mesh = Mesh::default();
+        // OBJ indexes positions and texture coordinates separately, so each
+        // distinct pair becomes one mesh vertex
+        let mut vertices: HashMap<(usize, Option<usize>), usize> = HashMap::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+            let keyword = fields.next();
+            let mut numbers = |count: usize| -> Result<Vec<f32>, (usize, String)> {
+                let values: Vec<f32> = fields
+                    .by_ref()
+                    .map(|field| field.parse().map_err(|_| (number, format!("invalid number {field:?}"))))
+                    .collect::<Result<_, _>>()?;
+                if values.len() < count {
+                    return Err((number, format!("expected at least {count} numbers")));
+                }
+                Ok(values)
+            };
+            match keyword {
+                Some("v") => {
+                    let values = numbers(3)?;
+                    positions.push(Vec3::new(values[0], values[1], values[2]));
+                    colors.push(match values[..] {
+                        [_, _, _, r, g, b, ..] => Some(Vec3::new(r, g, b)),
+                        _ => None,
+                    });
+                }
+                Some("vt") => {
+                    let values = numbers(1)?;
+                    texcoords.push(Vec2::new(values[0], values.get(1).copied().unwrap_or(0.0)));
+                }
+                Some("f") => {
+                    let fields: Vec<_> = fields.collect();
+                    if fields.len() < 3 {
+                        return Err((number, "face needs at least 3 vertices".to_owned()));
+                    }
+                    let mut indices = Vec::with_capacity(fields.len());
+                    for field in fields {
+                        let mut parts = field.split('/');
+                        let position = resolve(parts.next(), positions.len(), number)?
+                            .ok_or((number, format!("missing vertex index in {field:?}")))?;
+                        let texcoord = resolve(parts.next(), texcoords.len(), number)?;
+                        let index = *vertices.entry((position, texcoord)).or_insert_with(|| {
+                            mesh.positions.push(positions[position]);
+                            if let Some(texcoord) = texcoord {
+                                mesh.uvs.push(texcoords[texcoord]);
+                            }
+                            mesh.colors.push(colors[position].unwrap_or(Vec3::splat(1.0)));
+                            mesh.positions.len() - 1
+                        });
+                        indices.push(index);
+                    }
+                    for i in 1..indices.len() - 1 {
+                        mesh.triangles.push([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Attributes only make sense if every vertex has one
+        if mesh.uvs.len() != mesh.positions.len() {
+            mesh.uvs.clear();
+        }
+        if colors.iter().all(Option::is_none) {
+            mesh.colors.clear();
+        }
+        Ok(mesh)
+    }
+}
+
+/// Turns a 1-based (or negative, counting back from the end) OBJ index into
+/// a 0-based one. Empty fields, as in `f 1//1`, resolve to `None`.
+fn resolve(field: Option<&str>, count: usize, line: usize) -> Result<Option<usize>, (usize, String)> {
+    let field = match field {
+        Some(field) if !field.is_empty() => field,
+        _ => return Ok(None),
+    };
+    let index: i64 = field
+        .parse()
+        .map_err(|_| (line, format!("invalid index {field:?}")))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if !(0..count as i64).contains(&resolved) {
+        return Err((line, format!("index {index} out of range")));
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygons_become_triangle_fans() {
+        let mesh = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert!(mesh.uvs.is_empty() && mesh.colors.is_empty());
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let mesh = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\nf -4 -3 -1\n").unwrap();
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 1, 3]]);
+        assert_eq!(mesh.positions[3], Vec3::new(5.0, 5.0, 5.0));
+        assert_eq!(Mesh::parse_obj("v 0 0 0\nf -2 1 1").unwrap_err().0, 2);
+    }
+
+    #[test]
+    fn position_texcoord_pairs_are_separate_vertices() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvt 1 1\nf 1/1 2/2 3/3\nf 1/4 2/2 3/3\n";
+        let mesh = Mesh::parse_obj(source).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.uvs.len(), 4);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [3, 1, 2]]);
+        assert_eq!(mesh.uv(3), Vec2::new(1.0, 1.0));
+        assert_eq!(mesh.positions[3], mesh.positions[0]);
+    }
+
+    #[test]
+    fn vertex_colors_stay_with_their_vertex() {
+        let source = "v 0 0 0\nv 1 0 0 1 0 0\nv 0 1 0\nv 1 1 0 0 0 1\nf 1 2 3\nf 3 2 4\n";
+        let mesh = Mesh::parse_obj(source).unwrap();
+        assert_eq!(mesh.colors.len(), mesh.positions.len());
+        assert_eq!(mesh.color(0), Vec3::splat(1.0));
+        assert_eq!(mesh.color(1), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.color(2), Vec3::splat(1.0));
+        assert_eq!(mesh.color(3), Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn errors_carry_the_line_number() {
+        assert_eq!(Mesh::parse_obj("v 0 0 0\nv 1 x 0").unwrap_err().0, 2);
+        assert_eq!(Mesh::parse_obj("v 0 0 0\n\nf 1 1").unwrap_err().0, 3);
+        assert_eq!(Mesh::parse_obj("v 0 0 0\nf 1 2 1").unwrap_err().0, 2);
+    }
+}
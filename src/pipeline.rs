@@ -0,0 +1,245 @@
+#![allow(unused)]
+
+use crate::buffer::Buffer;
+use crate::linalg::{matrix::Mat4x4, vec2::Vec2, vec3::Vec3, vec4::Vec4};
+use crate::mesh::Mesh;
+use crate::raster::ScreenVertex;
+use crate::sprite::Sprite;
+use crate::tiles::TileRenderer;
+
+/// Model, view and projection transforms that take mesh vertices to the
+/// screen, drawing into the buffer's viewport.
+#[derive(Debug, Clone, Copy)]
+pub struct Pipeline {
+    pub model: Mat4x4<f32>,
+    pub view: Mat4x4<f32>,
+    pub projection: Mat4x4<f32>,
+    /// Skip triangles that face away from the camera.
+    pub cull_back_faces: bool,
+}
+
+/// A vertex in homogeneous clip space along with its attributes.
+#[derive(Debug, Clone, Copy)]
+pub struct ClipVertex {
+    pub position: Vec4<f32>,
+    pub color: Vec3<f32>,
+    pub uv: Vec2<f32>,
+}
+
+impl ClipVertex {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, t),
+            color: self.color.lerp(other.color, t),
+            uv: self.uv.lerp(other.uv, t),
+        }
+    }
+}
+
+impl Pipeline {
+    /// A camera at `z = 3` looking at the origin with a 60 degree field of view.
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            model: Mat4x4::identity(),
+            view: Mat4x4::look_at(
+                Vec3::new(0.0, 0.0, 3.0),
+                Vec3::zero(),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            projection: Mat4x4::perspective(60f32.to_radians(), aspect, 0.1, 100.0),
+            cull_back_faces: true,
+        }
+    }
+
+    pub fn mvp(&self) -> Mat4x4<f32> {
+        self.projection * self.view * self.model
+    }
+
+    /// Transforms every triangle to the screen, calling `draw` with the
+    /// projected corners of each visible polygon. Near-plane clipping can
+    /// turn a triangle into a quad.
+    fn project(&self, buffer: &Buffer, mesh: &Mesh, mut draw: impl FnMut(&[ScreenVertex])) {
+        let mvp = self.mvp();
+        let clip: Vec<_> = (0..mesh.positions.len())
+            .map(|i| ClipVertex {
+                position: mvp * mesh.positions[i].extend(1.0),
+                color: mesh.color(i),
+                uv: mesh.uv(i),
+            })
+            .collect();
+        let mut screen = Vec::with_capacity(4);
+        for &[a, b, c] in &mesh.triangles {
+            let polygon = clip_near(&[clip[a], clip[b], clip[c]]);
+            if polygon.len() < 3 {
+                continue;
+            }
+            screen.clear();
+            screen.extend(polygon.iter().map(|v| {
+                let ndc = v.position.perspective_divide();
+                ScreenVertex {
+                    position: to_screen(buffer, ndc.xy()),
+                    depth: ndc.z * 0.5 + 0.5,
+                    inv_w: 1.0 / v.position.w,
+                    color: v.color,
+                    uv: v.uv,
+                }
+            }));
+            if self.cull_back_faces && is_back_facing(&screen) {
+                continue;
+            }
+            draw(&screen);
+        }
+    }
+
+    /// Draws the edges of every visible triangle with `Buffer::tri`, or with
+    /// `Buffer::line` when near-plane clipping turned it into a quad.
+    pub fn draw_wireframe(&self, buffer: &mut Buffer, mesh: &Mesh) {
+        let mut polygons = Vec::new();
+        self.project(buffer, mesh, |polygon| {
+            let pixels: Vec<_> = polygon
+                .iter()
+                .map(|v| (v.position.x.round() as i32, v.position.y.round() as i32))
+                .collect();
+            polygons.push(pixels);
+        });
+        for pixels in polygons {
+            if let [p1, p2, p3] = pixels[..] {
+                buffer.tri(p1, p2, p3);
+            } else {
+                for i in 0..pixels.len() {
+                    buffer.line(pixels[i], pixels[(i + 1) % pixels.len()]);
+                }
+            }
+        }
+    }
+
+    /// Rasterizes every visible triangle with interpolated vertex colors and
+    /// an optional texture. Enable the buffer's depth buffer to sort the
+    /// triangles per pixel.
+    pub fn draw_solid(&self, buffer: &mut Buffer, mesh: &Mesh, texture: Option<&Sprite>) {
+        for triangle in self.triangles(buffer, mesh) {
+            buffer.raster_tri(triangle, texture);
+        }
+    }
+
+    /// Like `draw_solid`, but records the triangles for a `TileRenderer`
+    /// that will draw into `buffer`.
+    pub fn record_solid<'a>(&self, tiles: &mut TileRenderer<'a>, buffer: &Buffer, mesh: &Mesh, texture: Option<&'a Sprite>) {
+        for triangle in self.triangles(buffer, mesh) {
+            tiles.raster_tri(triangle, texture);
+        }
+    }
+
+    /// The visible triangles on screen, with clipped polygons fanned out.
+    fn triangles(&self, buffer: &Buffer, mesh: &Mesh) -> Vec<[ScreenVertex; 3]> {
+        let mut triangles = Vec::new();
+        self.project(buffer, mesh, |polygon| {
+            for i in 1..polygon.len() - 1 {
+                triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+            }
+        });
+        triangles
+    }
+}
+
+/// Clips a polygon in homogeneous clip space against the near plane
+/// `z = -w`, keeping the part in front of the camera.
+pub fn clip_near(polygon: &[ClipVertex]) -> Vec<ClipVertex> {
+    let distance = |v: &ClipVertex| v.position.z + v.position.w;
+    let mut output = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+        let (d_current, d_previous) = (distance(&current), distance(&previous));
+        if (d_current >= 0.0) != (d_previous >= 0.0) {
+            let t = d_previous / (d_previous - d_current);
+            output.push(previous.lerp(current, t));
+        }
+        if d_current >= 0.0 {
+            output.push(current);
+        }
+    }
+    output
+}
+
+/// Maps normalized device coordinates onto the buffer's viewport.
+pub fn to_screen(buffer: &Buffer, ndc: Vec2<f32>) -> Vec2<f32> {
+    let viewport = buffer.viewport();
+    Vec2::new(
+        viewport.x as f32 + (ndc.x + 1.0) / 2.0 * viewport.width as f32,
+        viewport.y as f32 + (1.0 - ndc.y) / 2.0 * viewport.height as f32,
+    )
+}
+
+/// Counter-clockwise polygons face the camera. Screen space has y pointing
+/// down, so a front face has a negative signed area there.
+fn is_back_facing(polygon: &[ScreenVertex]) -> bool {
+    let area: f32 = (0..polygon.len())
+        .map(|i| polygon[i].position.cross(polygon[(i + 1) % polygon.len()].position))
+        .sum();
+    area >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square facing the camera at depth `z`, colored `color`.
+    fn quad(z: f32, color: Vec3<f32>, counter_clockwise: bool) -> Mesh {
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let mut mesh = Mesh::new(
+            corners.map(|(x, y)| Vec3::new(x, y, z)).to_vec(),
+            vec![[0, 1, 2], [0, 2, 3]],
+        );
+        if !counter_clockwise {
+            mesh.triangles.iter_mut().for_each(|t| t.swap(1, 2));
+        }
+        mesh.colors = vec![color; 4];
+        mesh
+    }
+
+    fn center(buffer: &Buffer) -> u32 {
+        buffer[(16, 16)]
+    }
+
+    #[test]
+    fn back_faces_are_culled() {
+        let pipeline = Pipeline::new(1.0);
+        let mut buffer = Buffer::new(32, 32);
+        pipeline.draw_solid(&mut buffer, &quad(0.0, Vec3::splat(1.0), false), None);
+        assert!(buffer.iter().all(|&p| p == 0));
+        pipeline.draw_solid(&mut buffer, &quad(0.0, Vec3::splat(1.0), true), None);
+        assert_eq!(center(&buffer), 0xFFFFFF);
+        let both_sides = Pipeline {
+            cull_back_faces: false,
+            ..pipeline
+        };
+        buffer.clear();
+        both_sides.draw_solid(&mut buffer, &quad(0.0, Vec3::splat(1.0), false), None);
+        assert_eq!(center(&buffer), 0xFFFFFF);
+    }
+
+    #[test]
+    fn nearer_geometry_wins_with_depth() {
+        let pipeline = Pipeline::new(1.0);
+        let mut buffer = Buffer::new(32, 32);
+        buffer.enable_depth();
+        pipeline.draw_solid(&mut buffer, &quad(1.0, Vec3::new(1.0, 0.0, 0.0), true), None);
+        pipeline.draw_solid(&mut buffer, &quad(0.0, Vec3::new(0.0, 0.0, 1.0), true), None);
+        assert_eq!(center(&buffer), 0xFF0000);
+    }
+
+    #[test]
+    fn near_plane_cuts_triangles_into_quads() {
+        let vertex = |z: f32, w: f32| ClipVertex {
+            position: Vec4::new(0.0, z, z, w),
+            color: Vec3::zero(),
+            uv: Vec2::zero(),
+        };
+        let polygon = clip_near(&[vertex(-2.0, 1.0), vertex(0.0, 1.0), vertex(0.5, 1.0)]);
+        assert_eq!(polygon.len(), 4);
+        assert!(polygon.iter().all(|v| v.position.z + v.position.w >= -1e-6));
+        assert_eq!(clip_near(&[vertex(-2.0, 1.0), vertex(-3.0, 1.0), vertex(0.5, 1.0)]).len(), 3);
+        assert!(clip_near(&[vertex(-2.0, 1.0), vertex(-3.0, 1.0), vertex(-1.5, 1.0)]).is_empty());
+    }
+}
@@ -0,0 +1,702 @@
+#![allow(unused)]
+
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::buffer::Buffer;
+use crate::colors::Color;
+
+/// The largest `LUT_3D_SIZE` the `.cube` format allows.
+const MAX_LUT_SIZE: usize = 256;
+
+/// A 3D color lookup table for color grading. Entries are RGB in [0, 1],
+/// stored with red changing fastest as in `.cube` files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3D {
+    size: usize,
+    table: Vec<(f32, f32, f32)>,
+}
+
+impl Lut3D {
+    /// Samples `grade` on a `size`³ grid.
+    pub fn from_fn(size: usize, grade: impl Fn((f32, f32, f32)) -> (f32, f32, f32)) -> Self {
+        let size = size.max(2);
+        let step = |i: usize| i as f32 / (size - 1) as f32;
+        let mut table = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push(grade((step(r), step(g), step(b))));
+                }
+            }
+        }
+        Self { size, table }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Self::from_fn(size, |rgb| rgb)
+    }
+
+    pub fn load_cube(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse_cube(&fs::read_to_string(path)?).map_err(|(line, message)| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{line}: {message}", path.display()),
+            )
+        })
+    }
+
+    /// Parses an Adobe/Resolve `.cube` 3D LUT with the default [0, 1]
+    /// domain. Errors carry the 1-based line number.
+    pub fn parse_cube(source: &str) -> Result<Self, (usize, String)> {
+        let mut size = None;
+        let mut table = Vec::new();
+        let mut last_line = 0;
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            last_line = number;
+            let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+            match fields.next() {
+                None | Some("TITLE") | Some("DOMAIN_MIN") | Some("DOMAIN_MAX") => {}
+                Some("LUT_3D_SIZE") => {
+                    let value = fields.next().and_then(|f| f.parse::<usize>().ok());
+                    match value {
+                        Some(n) if (2..=MAX_LUT_SIZE).contains(&n) => size = Some(n),
+                        _ => return Err((number, format!("LUT_3D_SIZE needs a size from 2 to {MAX_LUT_SIZE}"))),
+                    }
+                }
+                Some(keyword) if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                    return Err((number, format!("unsupported keyword {keyword:?}")));
+                }
+                Some(first) => {
+                    let values: Vec<f32> = std::iter::once(first)
+                        .chain(fields)
+                        .map(|field| field.parse().map_err(|_| (number, format!("invalid number {field:?}"))))
+                        .collect::<Result<_, _>>()?;
+                    let [r, g, b] = values[..] else {
+                        return Err((number, "expected 3 numbers".to_owned()));
+                    };
+                    table.push((r, g, b));
+                }
+            }
+        }
+        let size = size.ok_or((last_line, "missing LUT_3D_SIZE".to_owned()))?;
+        let entries = size.pow(3);
+        if table.len() != entries {
+            return Err((last_line, format!("expected {entries} entries, found {}", table.len())));
+        }
+        Ok(Self { size, table })
+    }
+
+    /// Looks up a color with trilinear interpolation between grid points.
+    pub fn apply(&self, (r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+        let n = self.size - 1;
+        let split = |v: f32| {
+            let v = v.clamp(0.0, 1.0) * n as f32;
+            let i = (v as usize).min(n - 1);
+            (i, v - i as f32)
+        };
+        let ((ri, rt), (gi, gt), (bi, bt)) = (split(r), split(g), split(b));
+        let at = |r: usize, g: usize, b: usize| self.table[r + self.size * (g + self.size * b)];
+        let lerp = |a: (f32, f32, f32), b: (f32, f32, f32), t: f32| {
+            (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t, a.2 + (b.2 - a.2) * t)
+        };
+        let plane = |b: usize| {
+            let low = lerp(at(ri, gi, b), at(ri + 1, gi, b), rt);
+            let high = lerp(at(ri, gi + 1, b), at(ri + 1, gi + 1, b), rt);
+            lerp(low, high, gt)
+        };
+        lerp(plane(bi), plane(bi + 1), bt)
+    }
+}
+
+/// Pixel art upscaling filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upscale {
+    /// Repeats every pixel `n` times in each direction.
+    Nearest(usize),
+    /// AdvMAME2x/EPX: doubles the size, rounding off diagonal staircases
+    /// without introducing new colors.
+    Scale2x,
+    /// Maxim Stepin's hq2x: doubles the size, picking each quarter's blend
+    /// of the pixel and its neighbors from the pattern of neighbors that
+    /// differ from it in YUV.
+    Hq2x,
+}
+
+impl Upscale {
+    pub fn factor(&self) -> usize {
+        match *self {
+            Upscale::Nearest(n) => n.max(1),
+            Upscale::Scale2x | Upscale::Hq2x => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    /// Darkens every other row by `intensity` in [0, 1], like a CRT.
+    Scanlines { intensity: f32 },
+    /// Darkens towards the corners; `strength` 1 makes the corners black.
+    Vignette { strength: f32 },
+    BoxBlur { radius: usize },
+    GaussianBlur { sigma: f32 },
+    /// Adds a blurred copy of the pixels brighter than `threshold` (luma in
+    /// [0, 1]), scaled by `intensity`.
+    Bloom { threshold: f32, sigma: f32, intensity: f32 },
+    ColorGrade(Lut3D),
+    /// Enlarges the image; later effects run at the new size.
+    Upscale(Upscale),
+}
+
+impl Effect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Effect::Scanlines { .. } => "scanlines",
+            Effect::Vignette { .. } => "vignette",
+            Effect::BoxBlur { .. } => "box blur",
+            Effect::GaussianBlur { .. } => "gaussian blur",
+            Effect::Bloom { .. } => "bloom",
+            Effect::ColorGrade(_) => "color grade",
+            Effect::Upscale(Upscale::Nearest(_)) => "nearest upscale",
+            Effect::Upscale(Upscale::Scale2x) => "scale2x",
+            Effect::Upscale(Upscale::Hq2x) => "hq2x",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pass {
+    pub effect: Effect,
+    pub enabled: bool,
+}
+
+/// An ordered chain of effects run on a finished frame before it is shown.
+/// Each pass can be switched on and off while the game runs.
+#[derive(Debug, Clone, Default)]
+pub struct PostProcess {
+    passes: Vec<Pass>,
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an enabled pass, returning its index for toggling.
+    pub fn push(&mut self, effect: Effect) -> usize {
+        self.passes.push(Pass { effect, enabled: true });
+        self.passes.len() - 1
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        self.passes[index].enabled = enabled;
+    }
+
+    /// Flips a pass on or off, returning whether it is now enabled.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        let pass = &mut self.passes[index];
+        pass.enabled = !pass.enabled;
+        pass.enabled
+    }
+
+    /// How much larger the output is than the input.
+    pub fn scale(&self) -> usize {
+        self.enabled()
+            .filter_map(|effect| match effect {
+                Effect::Upscale(upscale) => Some(upscale.factor()),
+                _ => None,
+            })
+            .product()
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = &Effect> {
+        self.passes.iter().filter(|pass| pass.enabled).map(|pass| &pass.effect)
+    }
+
+    /// Runs the enabled passes on a copy of `source`. With none enabled,
+    /// `source` itself is returned and nothing is copied.
+    pub fn apply<'a>(&self, source: &'a Buffer) -> Cow<'a, Buffer> {
+        if self.enabled().next().is_none() {
+            return Cow::Borrowed(source);
+        }
+        let (width, height) = source.size();
+        let mut buffer = Buffer::new(width, height);
+        buffer.copy_from_slice(source);
+        for effect in self.enabled() {
+            match effect {
+                Effect::Scanlines { intensity } => scanlines(&mut buffer, *intensity),
+                Effect::Vignette { strength } => vignette(&mut buffer, *strength),
+                Effect::BoxBlur { radius } => {
+                    let mut image = Image::from_buffer(&buffer);
+                    image.box_blur(*radius);
+                    image.write(&mut buffer);
+                }
+                Effect::GaussianBlur { sigma } => {
+                    let mut image = Image::from_buffer(&buffer);
+                    image.gaussian_blur(*sigma);
+                    image.write(&mut buffer);
+                }
+                Effect::Bloom {
+                    threshold,
+                    sigma,
+                    intensity,
+                } => bloom(&mut buffer, *threshold, *sigma, *intensity),
+                Effect::ColorGrade(lut) => color_grade(&mut buffer, lut),
+                Effect::Upscale(upscale) => buffer = self::upscale(&buffer, *upscale),
+            }
+        }
+        Cow::Owned(buffer)
+    }
+}
+
+fn scale_rgb(pixel: u32, factor: f32) -> u32 {
+    let (r, g, b, _) = Color::from_u32(pixel).to_f32();
+    Color::from_f32(r * factor, g * factor, b * factor, 1.0).to_u32()
+}
+
+fn scanlines(buffer: &mut Buffer, intensity: f32) {
+    let (width, _) = buffer.size();
+    if width == 0 {
+        return;
+    }
+    let factor = 1.0 - intensity.clamp(0.0, 1.0);
+    for row in buffer.chunks_exact_mut(width).skip(1).step_by(2) {
+        row.iter_mut().for_each(|p| *p = scale_rgb(*p, factor));
+    }
+}
+
+fn vignette(buffer: &mut Buffer, strength: f32) {
+    let (width, height) = buffer.size();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let max_squared = cx * cx + cy * cy;
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let factor = 1.0 - strength * (dx * dx + dy * dy) / max_squared;
+            buffer[(x, y)] = scale_rgb(buffer[(x, y)], factor.max(0.0));
+        }
+    }
+}
+
+fn color_grade(buffer: &mut Buffer, lut: &Lut3D) {
+    for pixel in buffer.iter_mut() {
+        let (r, g, b, _) = Color::from_u32(*pixel).to_f32();
+        let (r, g, b) = lut.apply((r, g, b));
+        *pixel = Color::from_f32(r, g, b, 1.0).to_u32();
+    }
+}
+
+fn bloom(buffer: &mut Buffer, threshold: f32, sigma: f32, intensity: f32) {
+    let mut bright = Image::from_buffer(buffer);
+    for pixel in &mut bright.pixels {
+        let luma = 0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2];
+        if luma < threshold {
+            *pixel = [0.0; 3];
+        }
+    }
+    bright.gaussian_blur(sigma);
+    for (pixel, glow) in buffer.iter_mut().zip(&bright.pixels) {
+        let (r, g, b, _) = Color::from_u32(*pixel).to_f32();
+        let add = |c: f32, glow: f32| c + glow * intensity;
+        *pixel = Color::from_f32(add(r, glow[0]), add(g, glow[1]), add(b, glow[2]), 1.0).to_u32();
+    }
+}
+
+/// Float RGB copy of a buffer for filters that need more precision than
+/// 8 bits per channel between passes.
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Image {
+    fn from_buffer(buffer: &Buffer) -> Self {
+        let (width, height) = buffer.size();
+        let pixels = buffer
+            .iter()
+            .map(|&p| {
+                let (r, g, b, _) = Color::from_u32(p).to_f32();
+                [r, g, b]
+            })
+            .collect();
+        Self { width, height, pixels }
+    }
+
+    fn write(&self, buffer: &mut Buffer) {
+        for (pixel, &[r, g, b]) in buffer.iter_mut().zip(&self.pixels) {
+            *pixel = Color::from_f32(r, g, b, 1.0).to_u32();
+        }
+    }
+
+    /// Convolves rows, then columns, with a symmetric kernel given from its
+    /// center outwards. Edges repeat the outermost pixels.
+    fn separable(&mut self, kernel: &[f32]) {
+        let radius = kernel.len() as isize - 1;
+        let mut pass = |pixels: &[[f32; 3]], stride: usize, step: usize, count: usize, length: usize| {
+            let mut output = pixels.to_vec();
+            for line in 0..count {
+                let start = line * stride;
+                for i in 0..length {
+                    let mut sum = [0.0; 3];
+                    for k in -radius..=radius {
+                        let j = (i as isize + k).clamp(0, length as isize - 1) as usize;
+                        let weight = kernel[k.unsigned_abs()];
+                        let p = pixels[start + j * step];
+                        for c in 0..3 {
+                            sum[c] += p[c] * weight;
+                        }
+                    }
+                    output[start + i * step] = sum;
+                }
+            }
+            output
+        };
+        let rows = pass(&self.pixels, self.width, 1, self.height, self.width);
+        self.pixels = pass(&rows, 1, self.width, self.width, self.height);
+    }
+
+    fn box_blur(&mut self, radius: usize) {
+        if radius > 0 {
+            self.separable(&vec![1.0 / (2 * radius + 1) as f32; radius + 1]);
+        }
+    }
+
+    fn gaussian_blur(&mut self, sigma: f32) {
+        if sigma <= 0.0 {
+            return;
+        }
+        let radius = (sigma * 3.0).ceil() as usize;
+        let mut kernel: Vec<f32> = (0..=radius)
+            .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total = kernel[0] + 2.0 * kernel[1..].iter().sum::<f32>();
+        kernel.iter_mut().for_each(|w| *w /= total);
+        self.separable(&kernel);
+    }
+}
+
+fn upscale(source: &Buffer, filter: Upscale) -> Buffer {
+    let (width, height) = source.size();
+    let factor = filter.factor();
+    let mut output = Buffer::new(width * factor, height * factor);
+    // Neighbors past the edges repeat the edge pixels
+    let at = |x: isize, y: isize| {
+        source[(
+            x.clamp(0, width as isize - 1) as usize,
+            y.clamp(0, height as isize - 1) as usize,
+        )]
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as isize, y as isize);
+            let center = at(xi, yi);
+            let (ox, oy) = (x * factor, y * factor);
+            match filter {
+                Upscale::Nearest(_) => {
+                    for dy in 0..factor {
+                        let start = (oy + dy) * width * factor + ox;
+                        (*output)[start..start + factor].fill(center);
+                    }
+                }
+                Upscale::Scale2x => {
+                    let (up, left, right, down) = (at(xi, yi - 1), at(xi - 1, yi), at(xi + 1, yi), at(xi, yi + 1));
+                    let corner = |a: u32, b: u32, c: u32, d: u32| if a == b && a != c && b != d { a } else { center };
+                    output[(ox, oy)] = corner(left, up, down, right);
+                    output[(ox + 1, oy)] = corner(up, right, left, down);
+                    output[(ox, oy + 1)] = corner(left, down, up, right);
+                    output[(ox + 1, oy + 1)] = corner(down, right, left, up);
+                }
+                Upscale::Hq2x => {
+                    let mut w = [0; 9];
+                    for (i, pixel) in w.iter_mut().enumerate() {
+                        *pixel = at(xi + i as isize % 3 - 1, yi + i as isize / 3 - 1);
+                    }
+                    let differs = w.map(|pixel| !similar(center, pixel));
+                    for (rotation, (dx, dy)) in HQ2X_ROTATIONS.iter().zip([(0, 0), (1, 0), (0, 1), (1, 1)]) {
+                        output[(ox + dx, oy + dy)] = hq2x_quarter(&w, &differs, rotation);
+                    }
+                }
+            }
+        }
+    }
+    output
+}
+
+/// hqx's color similarity test, done in YUV with its fixed thresholds.
+fn similar(a: u32, b: u32) -> bool {
+    if a == b {
+        return true;
+    }
+    let yuv = |p: u32| {
+        let c = Color::from_u32(p);
+        let (r, g, b) = (c.r as f32, c.g as f32, c.b as f32);
+        (
+            0.299 * r + 0.587 * g + 0.114 * b,
+            -0.169 * r - 0.331 * g + 0.5 * b,
+            0.5 * r - 0.419 * g - 0.081 * b,
+        )
+    };
+    let (a, b) = (yuv(a), yuv(b));
+    (a.0 - b.0).abs() <= 48.0 && (a.1 - b.1).abs() <= 7.0 && (a.2 - b.2).abs() <= 6.0
+}
+
+fn weighted(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|&(_, w)| w).sum();
+    let channel = |shift: u32| colors.iter().map(|&(c, w)| ((c >> shift) & 0xFF) * w).sum::<u32>() / total;
+    (channel(16) << 16) | (channel(8) << 8) | channel(0)
+}
+
+/// A blend of the pixel (`w[4]`) and two of its neighbors, by weight; `w`
+/// is the 3x3 neighborhood row by row, seen from the top-left quarter.
+#[derive(Clone, Copy)]
+struct Mix {
+    center: u32,
+    a: (usize, u32),
+    b: (usize, u32),
+}
+
+#[derive(Clone, Copy)]
+enum Rule {
+    Always(Mix),
+    /// The first mix where the two neighbors differ, the second where they
+    /// are similar.
+    IfDiffer(usize, usize, Mix, Mix),
+}
+
+// The reference implementation's `PIXEL00_*` blends, by macro number
+const MIX_10: Mix = Mix { center: 3, a: (0, 1), b: (0, 0) };
+const MIX_11: Mix = Mix { center: 3, a: (3, 1), b: (0, 0) };
+const MIX_12: Mix = Mix { center: 3, a: (1, 1), b: (0, 0) };
+const MIX_20: Mix = Mix { center: 2, a: (3, 1), b: (1, 1) };
+const MIX_21: Mix = Mix { center: 2, a: (0, 1), b: (1, 1) };
+const MIX_22: Mix = Mix { center: 2, a: (0, 1), b: (3, 1) };
+const MIX_60: Mix = Mix { center: 5, a: (1, 2), b: (3, 1) };
+const MIX_61: Mix = Mix { center: 5, a: (3, 2), b: (1, 1) };
+const MIX_70: Mix = Mix { center: 6, a: (3, 1), b: (1, 1) };
+const MIX_90: Mix = Mix { center: 2, a: (3, 3), b: (1, 3) };
+const MIX_100: Mix = Mix { center: 14, a: (3, 1), b: (1, 1) };
+const MIX_0: Mix = Mix { center: 1, a: (0, 0), b: (0, 0) };
+
+const P10: Rule = Rule::Always(MIX_10);
+const P11: Rule = Rule::Always(MIX_11);
+const P12: Rule = Rule::Always(MIX_12);
+const P20: Rule = Rule::Always(MIX_20);
+const P21: Rule = Rule::Always(MIX_21);
+const P22: Rule = Rule::Always(MIX_22);
+// Conditional blends, on the neighbors to the left and above (E), above
+// and to the right (U), or below and to the left (L)
+const E10_20: Rule = Rule::IfDiffer(3, 1, MIX_10, MIX_20);
+const E0_20: Rule = Rule::IfDiffer(3, 1, MIX_0, MIX_20);
+const E10_70: Rule = Rule::IfDiffer(3, 1, MIX_10, MIX_70);
+const E0_90: Rule = Rule::IfDiffer(3, 1, MIX_0, MIX_90);
+const E10_90: Rule = Rule::IfDiffer(3, 1, MIX_10, MIX_90);
+const E0_100: Rule = Rule::IfDiffer(3, 1, MIX_0, MIX_100);
+const U11_60: Rule = Rule::IfDiffer(1, 5, MIX_11, MIX_60);
+const L12_61: Rule = Rule::IfDiffer(7, 3, MIX_12, MIX_61);
+
+/// hq2x's top-left quarter rule for every pattern of differing neighbors,
+/// with bits 0 to 7 for the neighbors in row order.
+#[rustfmt::skip]
+static HQ2X: [Rule; 256] = [
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_20,  E0_20,   P21,     P12,     E10_90,  E0_90,
+    P20,     P20,     P22,     U11_60,  P20,     P20,     P22,     U11_60,
+    P21,     P12,     E0_20,   E0_20,   P21,     P12,     P10,     E0_20,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_90,  E0_90,   P21,     P12,     E10_70,  E0_100,
+    P20,     P20,     P22,     U11_60,  P20,     P20,     P22,     U11_60,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     P10,     E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     L12_61,  E0_20,   E0_20,   P21,     L12_61,  E10_70,  E0_20,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     E10_70,  E0_20,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     L12_61,  P10,     E0_20,   P21,     L12_61,  P10,     E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     U11_60,
+    P21,     P12,     E10_70,  E0_20,   P21,     L12_61,  P10,     E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_20,  E0_20,   P21,     P12,     E10_90,  E0_90,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     E10_70,  E0_20,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_90,  E0_90,   P21,     P12,     E10_70,  E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_90,   P21,     P12,     P10,     E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     E10_70,  E0_90,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     P10,     E0_20,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     E10_70,  E0_20,   P21,     P12,     P10,     E0_100,
+    P20,     P20,     P22,     P11,     P20,     P20,     P22,     P11,
+    P21,     P12,     P10,     E0_20,   P21,     P12,     P10,     E0_100,
+];
+
+/// Where each quarter's neighbors sit, seen from the top-left quarter: the
+/// top-right, bottom-left and bottom-right quarters use the neighborhood
+/// rotated a quarter, three quarters and half a turn.
+const HQ2X_ROTATIONS: [[usize; 9]; 4] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8],
+    [2, 5, 8, 1, 4, 7, 0, 3, 6],
+    [6, 3, 0, 7, 4, 1, 8, 5, 2],
+    [8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+/// One output pixel of `Upscale::Hq2x`, from the pixel's neighborhood `w`
+/// and which of the neighbors differ from it.
+fn hq2x_quarter(w: &[u32; 9], differs: &[bool; 9], rotation: &[usize; 9]) -> u32 {
+    let pattern = [0, 1, 2, 3, 5, 6, 7, 8]
+        .iter()
+        .enumerate()
+        .filter(|&(_, &i)| differs[rotation[i]])
+        .fold(0, |pattern, (bit, _)| pattern | 1 << bit);
+    let w = rotation.map(|i| w[i]);
+    let mix = match HQ2X[pattern] {
+        Rule::Always(mix) => mix,
+        Rule::IfDiffer(a, b, differ, same) => if similar(w[a], w[b]) { same } else { differ },
+    };
+    weighted(&[(w[4], mix.center), (w[mix.a.0], mix.a.1), (w[mix.b.0], mix.b.1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, rows: &[&[u32]]) -> Buffer {
+        let mut buffer = Buffer::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                buffer[(x, y)] = pixel;
+            }
+        }
+        buffer
+    }
+
+    fn run(effect: Effect, source: &Buffer) -> Buffer {
+        let mut post = PostProcess::new();
+        post.push(effect);
+        post.apply(source).into_owned()
+    }
+
+    #[test]
+    fn nothing_enabled_borrows_the_source() {
+        let source = image(2, &[&[1, 2]]);
+        let mut post = PostProcess::new();
+        let index = post.push(Effect::Scanlines { intensity: 1.0 });
+        post.set_enabled(index, false);
+        assert!(matches!(post.apply(&source), Cow::Borrowed(_)));
+        assert!(post.toggle(index));
+        assert!(matches!(post.apply(&source), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn every_effect_handles_empty_buffers() {
+        let effects = || {
+            [
+                Effect::Scanlines { intensity: 0.5 },
+                Effect::Vignette { strength: 0.5 },
+                Effect::BoxBlur { radius: 2 },
+                Effect::GaussianBlur { sigma: 1.0 },
+                Effect::Bloom {
+                    threshold: 0.5,
+                    sigma: 1.0,
+                    intensity: 1.0,
+                },
+                Effect::ColorGrade(Lut3D::identity(2)),
+                Effect::Upscale(Upscale::Nearest(3)),
+                Effect::Upscale(Upscale::Scale2x),
+                Effect::Upscale(Upscale::Hq2x),
+            ]
+        };
+        for (width, height) in [(0, 0), (0, 3), (3, 0)] {
+            for effect in effects() {
+                let factor = match &effect {
+                    Effect::Upscale(upscale) => upscale.factor(),
+                    _ => 1,
+                };
+                let output = run(effect, &Buffer::new(width, height));
+                assert_eq!(output.size(), (width * factor, height * factor));
+            }
+        }
+    }
+
+    #[test]
+    fn scanlines_darken_every_other_row() {
+        let gray = Color::rgb(200, 100, 50).to_u32();
+        let output = run(Effect::Scanlines { intensity: 0.5 }, &image(1, &[&[gray], &[gray], &[gray]]));
+        assert_eq!(output[(0, 0)], gray);
+        assert_eq!(output[(0, 1)], Color::rgb(100, 50, 25).to_u32());
+        assert_eq!(output[(0, 2)], gray);
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners_more_than_the_center() {
+        let white = Color::WHITE.to_u32();
+        let output = run(Effect::Vignette { strength: 1.0 }, &image(3, &[&[white; 3], &[white; 3], &[white; 3]]));
+        let red = |pixel: u32| Color::from_u32(pixel).r;
+        assert!(red(output[(1, 1)]) > 240);
+        assert!(red(output[(0, 0)]) < red(output[(1, 0)]));
+    }
+
+    #[test]
+    fn identity_grade_keeps_colors() {
+        let source = image(3, &[&[0x000000, 0x8040C0, 0xFFFFFF]]);
+        let output = run(Effect::ColorGrade(Lut3D::identity(17)), &source);
+        assert_eq!(*output, *source);
+    }
+
+    #[test]
+    fn cube_sizes_are_checked_on_their_line() {
+        let lut = Lut3D::parse_cube("TITLE \"tiny\"\nLUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n");
+        assert_eq!(lut.unwrap(), Lut3D::identity(2));
+        for size in ["1", "257", "18446744073709551615", "99999999999999999999"] {
+            let error = Lut3D::parse_cube(&format!("TITLE \"huge\"\nLUT_3D_SIZE {size}\n0 0 0\n")).unwrap_err();
+            assert_eq!(error.0, 2, "{size}: {}", error.1);
+        }
+        assert_eq!(Lut3D::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").unwrap_err().0, 2);
+    }
+
+    #[test]
+    fn nearest_upscale_repeats_pixels() {
+        let mut post = PostProcess::new();
+        post.push(Effect::Upscale(Upscale::Nearest(3)));
+        post.push(Effect::Upscale(Upscale::Scale2x));
+        assert_eq!(post.scale(), 6);
+        let output = run(Effect::Upscale(Upscale::Nearest(3)), &image(2, &[&[1, 2]]));
+        assert_eq!(output.size(), (6, 3));
+        assert_eq!(*output, [1, 1, 1, 2, 2, 2, 1, 1, 1, 2, 2, 2, 1, 1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn scale2x_rounds_off_diagonal_steps() {
+        let (a, b, c) = (0x0000FF, 0x00FF00, 0xFF0000);
+        let output = run(Effect::Upscale(Upscale::Scale2x), &image(3, &[&[a, a, b], &[a, c, b], &[b, b, b]]));
+        assert_eq!(output.size(), (6, 6));
+        assert_eq!([output[(2, 2)], output[(3, 2)], output[(2, 3)], output[(3, 3)]], [a, c, c, b]);
+    }
+
+    #[test]
+    fn hq2x_keeps_flat_areas_and_follows_the_pattern_table() {
+        let flat = image(2, &[&[0x336699; 2], &[0x336699; 2]]);
+        let output = run(Effect::Upscale(Upscale::Hq2x), &flat);
+        assert_eq!(output.size(), (4, 4));
+        assert!(output.iter().all(|&pixel| pixel == 0x336699));
+
+        let (black, white) = (0x000000, 0xFFFFFF);
+        let output = run(Effect::Upscale(Upscale::Hq2x), &image(2, &[&[white, black], &[black, black]]));
+        // Seen from the white pixel's inner corner the differing neighbors
+        // make pattern 47, whose similar edge pair gives Interp10
+        assert_eq!(output[(0, 0)], white);
+        assert_eq!(output[(1, 1)], 0xDFDFDF);
+        assert_eq!([output[(1, 0)], output[(0, 1)], output[(2, 0)]], [white, white, black]);
+    }
+}
@@ -0,0 +1,194 @@
+#![allow(unused)]
+
+use crate::buffer::Buffer;
+use crate::colors::Color;
+use crate::linalg::{vec2::Vec2, vec3::Vec3};
+use crate::sprite::Sprite;
+
+/// A triangle corner after projection, ready for rasterization.
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenVertex {
+    /// Pixel position.
+    pub position: Vec2<f32>,
+    /// Depth in [0, 1], smaller is closer.
+    pub depth: f32,
+    /// Reciprocal of the clip-space `w`, used for perspective correction.
+    pub inv_w: f32,
+    /// RGB in [0, 1], multiplied with the texture.
+    pub color: Vec3<f32>,
+    pub uv: Vec2<f32>,
+}
+
+/// Nearest-neighbor texture lookup with wrapping. `v` points up, so (0, 0)
+/// is the bottom-left corner of the image as in OBJ files.
+pub fn sample(texture: &Sprite, uv: Vec2<f32>) -> Color {
+    let (width, height) = texture.size();
+    let u = uv.x - uv.x.floor();
+    let v = 1.0 - (uv.y - uv.y.floor());
+    let x = ((u * width as f32) as usize).min(width - 1);
+    let y = ((v * height as f32) as usize).min(height - 1);
+    texture.get(x, y)
+}
+
+fn edge(a: Vec2<f32>, b: Vec2<f32>, p: Vec2<f32>) -> f32 {
+    (b - a).cross(p - a)
+}
+
+/// An edge owns the pixels exactly on it only if it is a top or left edge,
+/// so triangles sharing an edge don't both draw it.
+fn is_top_left(a: Vec2<f32>, b: Vec2<f32>) -> bool {
+    let d = b - a;
+    (d.y == 0.0 && d.x > 0.0) || d.y < 0.0
+}
+
+impl Buffer {
+    /// Rasterizes a triangle with perspective-correct interpolation of vertex
+    /// colors and texture coordinates. When the depth buffer is enabled,
+    /// pixels behind already drawn geometry are skipped. Fully transparent
+    /// texels are discarded.
+    pub fn raster_tri(&mut self, vertices: [ScreenVertex; 3], texture: Option<&Sprite>) {
+        let [mut v0, mut v1, v2] = vertices;
+        let mut area = edge(v0.position, v1.position, v2.position);
+        if area == 0.0 {
+            return;
+        }
+        // Edge functions below assume a consistent winding
+        if area > 0.0 {
+            std::mem::swap(&mut v0, &mut v1);
+            area = -area;
+        }
+        let rect = self.clip_rect();
+        let xs = [v0.position.x, v1.position.x, v2.position.x];
+        let ys = [v0.position.y, v1.position.y, v2.position.y];
+        let min = |values: [f32; 3]| values.into_iter().fold(f32::INFINITY, f32::min);
+        let max = |values: [f32; 3]| values.into_iter().fold(f32::NEG_INFINITY, f32::max);
+        let start_x = ((min(xs) - 0.5).floor() as i32).max(rect.x);
+        let end_x = ((max(xs) + 0.5).ceil() as i32).min(rect.right());
+        let start_y = ((min(ys) - 0.5).floor() as i32).max(rect.y);
+        let end_y = ((max(ys) + 0.5).ceil() as i32).min(rect.bottom());
+
+        let edges = [(v1, v2), (v2, v0), (v0, v1)];
+        let bias = edges.map(|(a, b)| if is_top_left(b.position, a.position) { 0.0 } else { -f32::EPSILON });
+        let (alpha, mode) = (self.color().a, self.blend_mode());
+        for y in start_y..end_y {
+            for x in start_x..end_x {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let w = [0, 1, 2].map(|i| edge(edges[i].1.position, edges[i].0.position, p));
+                if (0..3).any(|i| w[i] + bias[i] < 0.0) {
+                    continue;
+                }
+                let b = w.map(|w| w / -area);
+                let depth = b[0] * v0.depth + b[1] * v1.depth + b[2] * v2.depth;
+                let (x, y) = (x as usize, y as usize);
+                if !self.depth_test((x, y), depth) {
+                    continue;
+                }
+                let pw = [b[0] * v0.inv_w, b[1] * v1.inv_w, b[2] * v2.inv_w];
+                let norm = 1.0 / (pw[0] + pw[1] + pw[2]);
+                let color = (v0.color * pw[0] + v1.color * pw[1] + v2.color * pw[2]) * norm;
+                let uv = (v0.uv * pw[0] + v1.uv * pw[1] + v2.uv * pw[2]) * norm;
+                let mut rgb = color;
+                if let Some(texture) = texture {
+                    let texel = sample(texture, uv);
+                    if texel.a == 0 {
+                        continue;
+                    }
+                    let (r, g, b, _) = texel.to_f32();
+                    rgb = rgb * Vec3::new(r, g, b);
+                }
+                let src = Color::from_f32(rgb.x, rgb.y, rgb.z, 1.0).with_alpha(alpha);
+                self[(x, y)] = mode.blend(self[(x, y)], src);
+                self.write_depth((x, y), depth);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blend::BlendMode;
+
+    fn vertex((x, y): (f32, f32), depth: f32, color: Vec3<f32>) -> ScreenVertex {
+        ScreenVertex {
+            position: Vec2::new(x, y),
+            depth,
+            inv_w: 1.0,
+            color,
+            uv: Vec2::new(x / 8.0, 1.0 - y / 8.0),
+        }
+    }
+
+    /// The two halves of the 8x8 square at the origin.
+    fn square(color: Vec3<f32>, depth: f32) -> [[ScreenVertex; 3]; 2] {
+        let corner = |x, y| vertex((x, y), depth, color);
+        [
+            [corner(0.0, 0.0), corner(8.0, 0.0), corner(8.0, 8.0)],
+            [corner(0.0, 0.0), corner(8.0, 8.0), corner(0.0, 8.0)],
+        ]
+    }
+
+    #[test]
+    fn depth_test_keeps_the_nearer_triangle() {
+        let near = square(Vec3::new(1.0, 0.0, 0.0), 0.2);
+        let far = square(Vec3::new(0.0, 0.0, 1.0), 0.8);
+        for order in [[near, far], [far, near]] {
+            let mut buffer = Buffer::new(8, 8);
+            buffer.enable_depth();
+            for triangle in order.iter().flatten() {
+                buffer.raster_tri(*triangle, None);
+            }
+            assert!(buffer.iter().all(|&p| p == 0xFF0000));
+        }
+        let mut buffer = Buffer::new(8, 8);
+        for triangle in [near, far].iter().flatten() {
+            buffer.raster_tri(*triangle, None);
+        }
+        assert!(buffer.iter().all(|&p| p == 0x0000FF));
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        let mut buffer = Buffer::new(10, 10);
+        buffer.set_blend_mode(BlendMode::Additive);
+        for triangle in square(Vec3::splat(0.2), 0.5) {
+            buffer.raster_tri(triangle, None);
+        }
+        for y in 0..10 {
+            for x in 0..10 {
+                let expected = if x < 8 && y < 8 { 0x333333 } else { 0 };
+                assert_eq!(buffer[(x, y)], expected, "at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn attributes_are_perspective_correct() {
+        let black = Vec3::zero();
+        let mut corners = [
+            vertex((0.0, 0.0), 0.5, black),
+            vertex((8.0, 0.0), 0.5, Vec3::new(1.0, 0.0, 0.0)),
+            vertex((0.0, 8.0), 0.5, black),
+        ];
+        let mut buffer = Buffer::new(8, 8);
+        buffer.raster_tri(corners, None);
+        assert_eq!(buffer[(3, 0)], Color::rgb(112, 0, 0).to_u32());
+        // The red corner is four times as far away, so it covers less of
+        // the triangle's near side
+        corners[1].inv_w = 0.25;
+        buffer.raster_tri(corners, None);
+        assert_eq!(buffer[(3, 0)], Color::rgb(42, 0, 0).to_u32());
+    }
+
+    #[test]
+    fn textures_tint_and_discard_transparent_texels() {
+        let texture = Sprite::from_pixels(2, 1, vec![Color::YELLOW.to_argb(), Color::RED.with_alpha(0).to_argb()]);
+        let mut buffer = Buffer::new(8, 8);
+        for triangle in square(Vec3::new(1.0, 0.0, 1.0), 0.5) {
+            buffer.raster_tri(triangle, Some(&texture));
+        }
+        assert_eq!(buffer[(1, 6)], 0xFF0000);
+        assert_eq!(buffer[(6, 1)], 0);
+        assert_eq!(sample(&texture, Vec2::new(1.25, -3.0)), Color::YELLOW);
+    }
+}
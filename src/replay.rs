@@ -0,0 +1,215 @@
+#![allow(unused)]
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::level::Level;
+use crate::world::{Events, GridWorld, Input};
+
+/// A recorded run of the `ty` game: where it started and the input of every
+/// tick, along with what happened on each so playing it back can check the
+/// game still behaves the same.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Replay {
+    /// Index of the level the run started on.
+    pub level: usize,
+    /// Seed for the game's randomness. The rules don't use any yet, so it
+    /// is only kept for runs recorded once they do.
+    pub seed: u64,
+    pub ticks: Vec<(Input, Events)>,
+}
+
+impl Replay {
+    pub fn new(level: usize, seed: u64) -> Self {
+        Self {
+            level,
+            seed,
+            ticks: Vec::new(),
+        }
+    }
+
+    /// Adds one tick of `GridWorld::step`.
+    pub fn record(&mut self, input: Input, events: Events) {
+        self.ticks.push((input, events));
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::parse(&fs::read_to_string(path)?).map_err(|(line, message)| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{line}: {message}", path.display()),
+            )
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Parses a replay. Lines starting with `;` are comments. `level <n>`
+    /// gives the 1-based level the run started on and `seed <n>` its seed.
+    /// After a line reading `ticks`, each line is one tick: the keys held,
+    /// `A`, `D` and `W` or `-` in their place, then any of `died`, `won` and
+    /// `next-level` for what happened. Errors carry the 1-based line number.
+    pub fn parse(source: &str) -> Result<Self, (usize, String)> {
+        let mut replay = Self::default();
+        let mut in_ticks = false;
+        for (number, line) in source.lines().enumerate() {
+            let number = number + 1;
+            let line = line.trim();
+            if line.starts_with(';') || line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let first = fields.next().unwrap_or_default();
+            if in_ticks {
+                let keys: Vec<char> = first.chars().collect();
+                let held = |index: usize, key: char| match keys[index] {
+                    c if c == key => Ok(true),
+                    '-' => Ok(false),
+                    c => Err((number, format!("expected {key:?} or '-', found {c:?}"))),
+                };
+                if keys.len() != 3 {
+                    return Err((number, format!("expected 3 keys like \"AD-\", found {first:?}")));
+                }
+                let input = Input {
+                    left: held(0, 'A')?,
+                    right: held(1, 'D')?,
+                    jump: held(2, 'W')?,
+                };
+                let mut events = Events::default();
+                for field in fields {
+                    match field {
+                        "died" => events.died = true,
+                        "won" => events.won = true,
+                        "next-level" => events.level_changed = true,
+                        _ => return Err((number, format!("unknown event {field:?}"))),
+                    }
+                }
+                replay.record(input, events);
+                continue;
+            }
+            let mut number_field = || {
+                let field = fields.next().ok_or((number, format!("{first} needs a number")))?;
+                field.parse::<u64>().map_err(|_| (number, format!("invalid number {field:?}")))
+            };
+            match first {
+                "ticks" => in_ticks = true,
+                "level" => match number_field()? {
+                    0 => return Err((number, "levels count from 1".to_owned())),
+                    level => replay.level = level as usize - 1,
+                },
+                "seed" => replay.seed = number_field()?,
+                keyword => return Err((number, format!("unknown keyword {keyword:?}"))),
+            }
+        }
+        if !in_ticks {
+            return Err((source.lines().count(), "missing ticks".to_owned()));
+        }
+        Ok(replay)
+    }
+
+    /// Runs the recording on `levels` without a window, stopping at the
+    /// first tick whose events differ from the recorded ones. Errors carry
+    /// the 0-based tick.
+    pub fn play(&self, levels: Vec<Level>) -> Result<GridWorld, (usize, String)> {
+        if self.level >= levels.len() {
+            return Err((0, format!("starts on level {} of {}", self.level + 1, levels.len())));
+        }
+        let mut world = GridWorld::starting_at(levels, self.level);
+        for (tick, &(input, expected)) in self.ticks.iter().enumerate() {
+            let events = world.step(input);
+            if events != expected {
+                return Err((tick, format!("expected {expected:?}, got {events:?}")));
+            }
+        }
+        Ok(world)
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "; ty replay, one line per tick: keys held, then events")?;
+        writeln!(f, "level {}", self.level + 1)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "ticks")?;
+        for (input, events) in &self.ticks {
+            let key = |held: bool, key: char| if held { key } else { '-' };
+            write!(f, "{}{}{}", key(input.left, 'A'), key(input.right, 'D'), key(input.jump, 'W'))?;
+            for (happened, name) in [(events.died, "died"), (events.won, "won"), (events.level_changed, "next-level")] {
+                if happened {
+                    write!(f, " {name}")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_run() -> (Replay, Vec<Level>) {
+        let replay = Replay::load("replays/full-run.txt").unwrap();
+        (replay, Level::load_dir("levels").unwrap())
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let mut replay = Replay::new(2, 42);
+        replay.record(Input::default(), Events::default());
+        replay.record(
+            Input {
+                left: true,
+                jump: true,
+                ..Default::default()
+            },
+            Events {
+                died: true,
+                ..Default::default()
+            },
+        );
+        replay.record(
+            Input {
+                right: true,
+                ..Default::default()
+            },
+            Events {
+                won: true,
+                level_changed: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(Replay::parse(&replay.to_string()), Ok(replay));
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        assert_eq!(Replay::parse("level 1\nticks\n---\nAX-").unwrap_err().0, 4);
+        assert_eq!(Replay::parse("level 0\nticks").unwrap_err().0, 1);
+        assert_eq!(Replay::parse("ticks\n-D- fell").unwrap_err().0, 2);
+        assert!(Replay::parse("level 1\n").is_err());
+    }
+
+    #[test]
+    fn recorded_full_run_finishes_the_game() {
+        let (replay, levels) = full_run();
+        let world = replay.play(levels).unwrap();
+        assert!(world.is_finished());
+        assert!(replay.ticks.iter().any(|(_, events)| events.died));
+    }
+
+    #[test]
+    fn changed_input_is_caught_where_the_events_differ() {
+        let (mut replay, levels) = full_run();
+        let death = replay.ticks.iter().position(|(_, events)| events.died).unwrap();
+        replay.ticks[0].0 = Input::default();
+        let (tick, _) = replay.play(levels).map(|_| ()).unwrap_err();
+        assert!(tick > 0 && tick <= death + 1, "diverged at tick {tick}");
+    }
+}
@@ -0,0 +1,420 @@
+#![allow(unused)]
+
+use std::f32::consts::TAU;
+
+use crate::buffer::Buffer;
+use crate::clip::Rect;
+use crate::linalg::vec2::Vec2;
+use crate::stroke::StrokeStyle;
+
+/// Maximum distance, in pixels, between a curve and its flattened polyline.
+pub const TOLERANCE: f32 = 0.25;
+
+/// The pixels of the first quadrant of an ellipse, as offsets from the
+/// center with `y` pointing away from it. Each offset appears once.
+fn ellipse_quadrant(rx: i32, ry: i32) -> Vec<(i32, i32)> {
+    if ry == 0 {
+        return (0..=rx).map(|x| (x, 0)).collect();
+    }
+    let (rx, ry) = (rx as i64, ry as i64);
+    let (a2, b2) = (rx * rx, ry * ry);
+    let mut points = Vec::new();
+    let (mut x, mut y) = (0, ry);
+    // Region 1: the slope is shallower than -1, so step x every pixel
+    let mut dx = 0;
+    let mut dy = 2 * a2 * y;
+    let mut d = 4 * b2 - 4 * a2 * ry + a2;
+    while dx < dy {
+        points.push((x as i32, y as i32));
+        x += 1;
+        dx += 2 * b2;
+        if d < 0 {
+            d += 4 * (dx + b2);
+        } else {
+            y -= 1;
+            dy -= 2 * a2;
+            d += 4 * (dx - dy + b2);
+        }
+    }
+    // Region 2: step y every pixel until reaching the horizontal axis
+    let mut d = b2 * (2 * x + 1) * (2 * x + 1) + 4 * a2 * (y - 1) * (y - 1) - 4 * a2 * b2;
+    while y >= 0 {
+        points.push((x as i32, y as i32));
+        y -= 1;
+        dy -= 2 * a2;
+        if d > 0 {
+            d += 4 * (a2 - dy);
+        } else {
+            x += 1;
+            dx += 2 * b2;
+            d += 4 * (dx - dy + a2);
+        }
+    }
+    points
+}
+
+/// The first quadrant of a midpoint circle, built from one octant.
+fn circle_quadrant(radius: i32) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+    while x >= y {
+        points.push((x, y));
+        points.push((y, x));
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+/// Number of segments that keep a flattened arc within `TOLERANCE` of the
+/// true one.
+pub fn arc_segments(radius: f32, sweep: f32) -> usize {
+    if radius <= TOLERANCE {
+        return 1;
+    }
+    let step = 2.0 * (1.0 - TOLERANCE / radius).acos();
+    ((sweep.abs() / step).ceil() as usize).clamp(1, 1024)
+}
+
+/// Points along an elliptical arc from `start` to `end` radians, measured
+/// from the positive x axis towards the positive y axis.
+pub fn arc_points(center: (f32, f32), (rx, ry): (f32, f32), start: f32, end: f32, segments: usize) -> Vec<(f32, f32)> {
+    let segments = segments.max(1);
+    (0..=segments)
+        .map(|i| {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            (center.0 + rx * angle.cos(), center.1 + ry * angle.sin())
+        })
+        .collect()
+}
+
+/// Outline of a rectangle between two corners with its corners rounded to
+/// `radius`, clamped to half the shorter side.
+pub fn rounded_rect_points(p1: (f32, f32), p2: (f32, f32), radius: f32, segments: usize) -> Vec<(f32, f32)> {
+    let (x0, x1) = (p1.0.min(p2.0), p1.0.max(p2.0));
+    let (y0, y1) = (p1.1.min(p2.1), p1.1.max(p2.1));
+    let r = radius.clamp(0.0, (x1 - x0).min(y1 - y0) / 2.0);
+    let quarter = TAU / 4.0;
+    let corners = [(x1 - r, y1 - r), (x0 + r, y1 - r), (x0 + r, y0 + r), (x1 - r, y0 + r)];
+    let mut points = Vec::new();
+    for (i, center) in corners.into_iter().enumerate() {
+        let start = i as f32 * quarter;
+        points.extend(arc_points(center, (r, r), start, start + quarter, segments));
+    }
+    points.dedup();
+    points
+}
+
+/// Flattens a quadratic Bézier curve into a polyline, including both ends.
+pub fn flatten_quadratic(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), tolerance: f32) -> Vec<(f32, f32)> {
+    // Every quadratic is a cubic with its control point split two thirds
+    let (a, b, c) = (Vec2::from(p0), Vec2::from(p1), Vec2::from(p2));
+    let c1 = a + (b - a) * (2.0 / 3.0);
+    let c2 = c + (b - c) * (2.0 / 3.0);
+    flatten_cubic(p0, c1.into(), c2.into(), p2, tolerance)
+}
+
+/// Flattens a cubic Bézier curve into a polyline by subdividing it until
+/// each piece is flat to within `tolerance`, so tight bends get more
+/// segments than gentle ones.
+pub fn flatten_cubic(
+    p0: (f32, f32),
+    p1: (f32, f32),
+    p2: (f32, f32),
+    p3: (f32, f32),
+    tolerance: f32,
+) -> Vec<(f32, f32)> {
+    let mut points = vec![p0];
+    subdivide([p0, p1, p2, p3].map(Vec2::from), tolerance.max(0.01), 0, &mut points);
+    points
+}
+
+fn subdivide(curve: [Vec2<f32>; 4], tolerance: f32, depth: u32, points: &mut Vec<(f32, f32)>) {
+    let [p0, p1, p2, p3] = curve;
+    // The control points bound the curve, so their distance from the chord
+    // bounds its deviation from a straight line
+    let chord = p3 - p0;
+    let length = chord.length();
+    let distance = |p: Vec2<f32>| {
+        if length > 0.0 {
+            chord.cross(p - p0).abs() / length
+        } else {
+            p.distance(p0)
+        }
+    };
+    if depth >= 16 || distance(p1).max(distance(p2)) <= tolerance {
+        points.push(p3.into());
+        return;
+    }
+    // de Casteljau split at t = 0.5
+    let p01 = (p0 + p1) * 0.5;
+    let p12 = (p1 + p2) * 0.5;
+    let p23 = (p2 + p3) * 0.5;
+    let p012 = (p01 + p12) * 0.5;
+    let p123 = (p12 + p23) * 0.5;
+    let mid = (p012 + p123) * 0.5;
+    subdivide([p0, p01, p012, mid], tolerance, depth + 1, points);
+    subdivide([mid, p123, p23, p3], tolerance, depth + 1, points);
+}
+
+/// Corners of `rect` as pixel edges, moved `inset` towards the inside.
+fn rounded_rect_pixels(rect: Rect, radius: f32, inset: f32) -> Vec<(f32, f32)> {
+    let p1 = (rect.x as f32 + inset, rect.y as f32 + inset);
+    let p2 = (rect.right() as f32 - inset, rect.bottom() as f32 - inset);
+    rounded_rect_points(p1, p2, radius, arc_segments(radius, TAU / 4.0))
+}
+
+impl Buffer {
+    fn quadrant_outline(&mut self, (cx, cy): (i32, i32), quadrant: &[(i32, i32)]) {
+        for &(x, y) in quadrant {
+            let xs: &[i32] = if x == 0 { &[0] } else { &[x, -x] };
+            let ys: &[i32] = if y == 0 { &[0] } else { &[y, -y] };
+            for &dx in xs {
+                for &dy in ys {
+                    self.pixel((cx + dx, cy + dy));
+                }
+            }
+        }
+    }
+
+    /// Fills one span per row, so translucent fills blend every pixel once.
+    /// Only rows inside the clip rectangle are gathered.
+    fn quadrant_fill(&mut self, (cx, cy): (i32, i32), quadrant: &[(i32, i32)]) {
+        let clip = self.clip_rect();
+        let height = quadrant.iter().map(|p| p.1).max().unwrap_or(0);
+        // Distances from the center row to the clip's first and last rows
+        let (top, bottom) = (clip.y - cy, clip.bottom() - 1 - cy);
+        let nearest = if top <= 0 && bottom >= 0 { 0 } else { top.abs().min(bottom.abs()) };
+        let farthest = top.abs().max(bottom.abs()).min(height);
+        if nearest > farthest {
+            return;
+        }
+        let mut half_widths = vec![0; (farthest - nearest) as usize + 1];
+        for &(x, y) in quadrant {
+            if (nearest..=farthest).contains(&y) {
+                let half = &mut half_widths[(y - nearest) as usize];
+                *half = (*half).max(x);
+            }
+        }
+        for (dy, &half) in (nearest..).zip(&half_widths) {
+            let rows: &[i32] = if dy == 0 { &[cy] } else { &[cy - dy, cy + dy] };
+            for &y in rows {
+                self.fill_span(y, cx - half, cx + half + 1);
+            }
+        }
+    }
+
+    /// Largest radius drawn with the midpoint algorithm, about the size of
+    /// the clip rectangle. Larger curves are drawn as flattened polygons,
+    /// which keeps the work bounded by the clip instead of the radius.
+    pub fn max_midpoint_radius(&self) -> i32 {
+        let clip = self.clip_rect();
+        // Well below where the squared radii in the error terms overflow
+        (clip.width + clip.height).clamp(0, 1 << 14)
+    }
+
+    /// Draws an ellipse from its midpoint `quadrant` when the radii allow
+    /// it, otherwise from a flattened polygon through the same pixel
+    /// centers.
+    fn ellipse_with(&mut self, center: (i32, i32), rx: i32, ry: i32, fill: bool, quadrant: fn(i32, i32) -> Vec<(i32, i32)>) {
+        let clip = self.clip_rect();
+        let (cx, cy, rx, ry) = (center.0 as i64, center.1 as i64, rx as i64, ry as i64);
+        let outside = cx + rx < clip.x as i64
+            || cx - rx >= clip.right() as i64
+            || cy + ry < clip.y as i64
+            || cy - ry >= clip.bottom() as i64;
+        if rx < 0 || ry < 0 || outside {
+            return;
+        }
+        let limit = self.max_midpoint_radius() as i64;
+        if rx <= limit && ry <= limit {
+            let quadrant = quadrant(rx as i32, ry as i32);
+            if fill {
+                self.quadrant_fill(center, &quadrant);
+            } else {
+                self.quadrant_outline(center, &quadrant);
+            }
+            return;
+        }
+        let (rx, ry) = (rx as f32, ry as f32);
+        let center = (cx as f32 + 0.5, cy as f32 + 0.5);
+        let mut points = arc_points(center, (rx + 0.5, ry + 0.5), 0.0, TAU, arc_segments(rx.max(ry), TAU));
+        points.pop();
+        if fill {
+            self.fill_polygon_f32(&points);
+        } else {
+            self.stroke(&points, true, &StrokeStyle::default());
+        }
+    }
+
+    /// Midpoint circle outline.
+    pub fn circle(&mut self, center: (i32, i32), radius: i32) {
+        self.ellipse_with(center, radius, radius, false, |radius, _| circle_quadrant(radius));
+    }
+
+    pub fn fill_circle(&mut self, center: (i32, i32), radius: i32) {
+        self.ellipse_with(center, radius, radius, true, |radius, _| circle_quadrant(radius));
+    }
+
+    /// Midpoint outline of an axis-aligned ellipse with radii `rx` and `ry`.
+    pub fn ellipse(&mut self, center: (i32, i32), rx: i32, ry: i32) {
+        self.ellipse_with(center, rx, ry, false, ellipse_quadrant);
+    }
+
+    pub fn fill_ellipse(&mut self, center: (i32, i32), rx: i32, ry: i32) {
+        self.ellipse_with(center, rx, ry, true, ellipse_quadrant);
+    }
+
+    fn polyline(&mut self, points: &[(f32, f32)]) {
+        let pixel = |(x, y): (f32, f32)| (x.floor() as i32, y.floor() as i32);
+        for pair in points.windows(2) {
+            self.line(pixel(pair[0]), pixel(pair[1]));
+        }
+    }
+
+    /// Circular arc from `start` to `end` radians, counter-clockwise on the
+    /// screen, in pixel units like `line_aa`.
+    pub fn arc(&mut self, center: (f32, f32), radius: f32, start: f32, end: f32) {
+        // Negating the y radius turns y-down angles counter-clockwise
+        let points = arc_points(center, (radius, -radius), start, end, arc_segments(radius, end - start));
+        self.polyline(&points);
+    }
+
+    pub fn rounded_rect(&mut self, rect: Rect, radius: f32) {
+        let points = rounded_rect_pixels(rect, radius, 0.5);
+        let pixel = |(x, y): (f32, f32)| (x.floor() as i32, y.floor() as i32);
+        for i in 0..points.len() {
+            self.line(pixel(points[i]), pixel(points[(i + 1) % points.len()]));
+        }
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: f32) {
+        let points = rounded_rect_pixels(rect, radius, 0.0);
+        self.fill_polygon_f32(&points);
+    }
+
+    /// Quadratic Bézier curve through `p0` and `p2`, pulled towards `p1`.
+    pub fn quadratic(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) {
+        self.polyline(&flatten_quadratic(p0, p1, p2, TOLERANCE));
+    }
+
+    /// Cubic Bézier curve through `p0` and `p3` with control points `p1`, `p2`.
+    pub fn cubic(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        self.polyline(&flatten_cubic(p0, p1, p2, p3, TOLERANCE));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    /// Lit pixels of a translucent drawing on a 21x21 buffer, checking that
+    /// each was blended exactly once.
+    fn lit(draw: impl FnOnce(&mut Buffer)) -> Vec<(i32, i32)> {
+        let mut buffer = Buffer::new(21, 21);
+        buffer.set_alpha(128);
+        draw(&mut buffer);
+        let mut pixels = Vec::new();
+        for y in 0..21 {
+            for x in 0..21 {
+                match buffer[(x, y)] {
+                    0 => {}
+                    0x808080 => pixels.push((x as i32, y as i32)),
+                    other => panic!("({x}, {y}) blended to {other:06x}"),
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Lit pixels of an opaque drawing on a 21x21 buffer, for polylines
+    /// whose segments share their end pixels.
+    fn opaque(draw: impl FnOnce(&mut Buffer)) -> Vec<(i32, i32)> {
+        let mut buffer = Buffer::new(21, 21);
+        draw(&mut buffer);
+        (0..21 * 21).filter(|&i| buffer[(i % 21, i / 21)] != 0).map(|i| (i as i32 % 21, i as i32 / 21)).collect()
+    }
+
+    fn is_symmetric(pixels: &[(i32, i32)]) -> bool {
+        pixels.iter().all(|&(x, y)| pixels.contains(&(20 - x, y)) && pixels.contains(&(x, 20 - y)))
+    }
+
+    #[test]
+    fn circles_reach_their_radius() {
+        let outline = lit(|b| b.circle((10, 10), 5));
+        assert!(is_symmetric(&outline));
+        assert!([(15, 10), (5, 10), (10, 15), (10, 5)].iter().all(|p| outline.contains(p)));
+        assert!(!outline.contains(&(10, 10)) && !outline.contains(&(14, 14)));
+        let filled = lit(|b| b.fill_circle((10, 10), 5));
+        assert!(is_symmetric(&filled));
+        assert!(filled.contains(&(10, 10)) && !filled.contains(&(14, 14)));
+        assert!(outline.iter().all(|p| filled.contains(p)));
+    }
+
+    #[test]
+    fn ellipses_use_both_radii() {
+        let outline = lit(|b| b.ellipse((10, 10), 6, 2));
+        assert!(is_symmetric(&outline));
+        assert!([(16, 10), (4, 10), (10, 12), (10, 8)].iter().all(|p| outline.contains(p)));
+        assert!(!outline.contains(&(17, 10)) && !outline.contains(&(10, 13)));
+        let filled = lit(|b| b.fill_ellipse((10, 10), 6, 2));
+        assert_eq!(filled.iter().filter(|p| p.1 == 10).count(), 13);
+        assert_eq!(filled.iter().map(|p| p.1).max(), Some(12));
+        assert_eq!(lit(|b| b.ellipse((10, 10), 3, 0)), (7..=13).map(|x| (x, 10)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn huge_ellipses_are_bounded_by_the_clip() {
+        assert_eq!(lit(|b| b.fill_circle((10, 10), 1_000_000)).len(), 21 * 21);
+        // Only the flat top of the circle reaches into the buffer
+        let cap = lit(|b| b.fill_circle((10, 1_000_010), 1_000_000));
+        assert_eq!(cap, (10..21).flat_map(|y| (0..21).map(move |x| (x, y))).collect::<Vec<_>>());
+        assert!(lit(|b| b.ellipse((10, 10), i32::MAX, i32::MAX)).is_empty());
+        assert!(lit(|b| b.fill_ellipse((i32::MAX, i32::MIN), 5, 5)).is_empty());
+    }
+
+    #[test]
+    fn arcs_and_rounded_rects() {
+        let arc = opaque(|b| b.arc((10.5, 10.5), 5.0, 0.0, FRAC_PI_2));
+        assert!(arc.contains(&(15, 10)) && arc.contains(&(10, 5)));
+        assert!(arc.iter().all(|&(x, y)| x >= 10 && y <= 10));
+        let panel = lit(|b| b.fill_rounded_rect(Rect::new(0, 0, 10, 10), 4.0));
+        assert!(panel.contains(&(5, 0)) && panel.contains(&(0, 5)) && panel.contains(&(9, 5)));
+        assert!(!panel.contains(&(0, 0)) && !panel.contains(&(9, 9)));
+        let segments = arc_segments(50.0, TAU);
+        assert!(50.0 * (1.0 - (TAU / segments as f32 / 2.0).cos()) <= TOLERANCE + 1e-4);
+    }
+
+    #[test]
+    fn bezier_flattening_stays_within_tolerance() {
+        let (p0, p1, p2, p3) = ((0.0, 0.0), (0.0, 40.0), (60.0, -20.0), (50.0, 30.0));
+        let points = flatten_cubic(p0, p1, p2, p3, TOLERANCE);
+        assert_eq!((points[0], points[points.len() - 1]), (p0, p3));
+        let curve: Vec<_> = (0..=2000)
+            .map(|i| {
+                let t = i as f32 / 2000.0;
+                let s = 1.0 - t;
+                let [a, b, c, d] = [p0, p1, p2, p3].map(Vec2::from);
+                a * (s * s * s) + b * (3.0 * s * s * t) + c * (3.0 * s * t * t) + d * (t * t * t)
+            })
+            .collect();
+        for pair in points.windows(2) {
+            let middle = (Vec2::from(pair[0]) + Vec2::from(pair[1])) * 0.5;
+            let distance = curve.iter().map(|p| p.distance(middle)).fold(f32::INFINITY, f32::min);
+            assert!(distance <= TOLERANCE + 0.05, "segment is {distance} from the curve");
+        }
+        assert_eq!(flatten_quadratic((0.0, 0.0), (5.0, 5.0), (10.0, 10.0), TOLERANCE).len(), 2);
+        let gentle = flatten_quadratic((0.0, 0.0), (20.0, 2.0), (40.0, 0.0), TOLERANCE);
+        let tight = flatten_quadratic((0.0, 0.0), (20.0, 40.0), (40.0, 0.0), TOLERANCE);
+        assert!(gentle.len() < tight.len());
+    }
+}
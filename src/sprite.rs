@@ -0,0 +1,204 @@
+#![allow(unused)]
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::clip::Rect;
+use crate::colors::Color;
+
+/// A decoded image stored as packed `0xAARRGGBB` pixels, row by row.
+pub struct Sprite {
+    pixels: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+/// How a sprite's pixels are combined with the buffer when blitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transparency {
+    /// Composite using each pixel's alpha channel.
+    #[default]
+    Alpha,
+    /// Skip pixels whose RGB matches the key's; draw the rest opaque.
+    ColorKey(Color),
+    /// Copy every pixel as-is.
+    Opaque,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Blit {
+    /// Region of the sprite to draw, or the whole sprite when `None`.
+    pub source: Option<Rect>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub transparency: Transparency,
+}
+
+impl Sprite {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::from_pixels(width, height, vec![0; width * height])
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count doesn't match size");
+        Self {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    /// Decodes a PNG file of any bit depth and color type into ARGB pixels.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let data = &data[..info.buffer_size()];
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => data.iter().map(|&v| Color::rgb(v, v, v).to_argb()).collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .map(|p| Color::rgba(p[0], p[0], p[0], p[1]).to_argb())
+                .collect(),
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .map(|p| Color::rgb(p[0], p[1], p[2]).to_argb())
+                .collect(),
+            png::ColorType::Rgba => data
+                .chunks_exact(4)
+                .map(|p| Color::rgba(p[0], p[1], p[2], p[3]).to_argb())
+                .collect(),
+            png::ColorType::Indexed => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "indexed PNG was not expanded",
+                ))
+            }
+        };
+        Ok(Self::from_pixels(info.width as usize, info.height as usize, pixels))
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        Color::from_argb(self.pixels[y * self.width + x])
+    }
+
+    /// A copy with every pixel whose RGB matches a `from` color replaced by
+    /// the paired `to` color, keeping the pixel's alpha.
+    pub fn swap_colors(&self, swaps: &[(Color, Color)]) -> Sprite {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|&argb| {
+                let pixel = Color::from_argb(argb);
+                swaps
+                    .iter()
+                    .find(|(from, _)| from.to_u32() == pixel.to_u32())
+                    .map_or(argb, |(_, to)| to.with_alpha(pixel.a).to_argb())
+            })
+            .collect();
+        Sprite::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    /// Red, green on top, blue and a see-through pixel below.
+    fn checker() -> Sprite {
+        let pixels = [Color::RED, Color::GREEN, Color::BLUE, Color::WHITE.with_alpha(0)];
+        Sprite::from_pixels(2, 2, pixels.iter().map(|c| c.to_argb()).collect())
+    }
+
+    fn blitted(blit: &Blit) -> Buffer {
+        let mut buffer = Buffer::new(6, 6);
+        buffer.set_color(Color::GRAY);
+        buffer.fill();
+        buffer.blit(&checker(), Rect::new(1, 1, 4, 4), blit);
+        buffer
+    }
+
+    #[test]
+    fn blit_scales_to_the_destination() {
+        let buffer = blitted(&Blit::default());
+        assert_eq!(buffer[(0, 0)], Color::GRAY.to_u32());
+        assert_eq!(buffer[(1, 1)], Color::RED.to_u32());
+        assert_eq!(buffer[(2, 2)], Color::RED.to_u32());
+        assert_eq!(buffer[(4, 1)], Color::GREEN.to_u32());
+        assert_eq!(buffer[(1, 4)], Color::BLUE.to_u32());
+        assert_eq!(buffer[(4, 4)], Color::GRAY.to_u32());
+        assert_eq!(buffer[(5, 5)], Color::GRAY.to_u32());
+    }
+
+    #[test]
+    fn blit_flips() {
+        let buffer = blitted(&Blit {
+            flip_x: true,
+            flip_y: true,
+            ..Default::default()
+        });
+        assert_eq!(buffer[(1, 1)], Color::GRAY.to_u32());
+        assert_eq!(buffer[(4, 1)], Color::BLUE.to_u32());
+        assert_eq!(buffer[(1, 4)], Color::GREEN.to_u32());
+        assert_eq!(buffer[(4, 4)], Color::RED.to_u32());
+    }
+
+    #[test]
+    fn blit_transparency_modes() {
+        let keyed = blitted(&Blit {
+            transparency: Transparency::ColorKey(Color::BLUE),
+            ..Default::default()
+        });
+        assert_eq!(keyed[(1, 4)], Color::GRAY.to_u32());
+        assert_eq!(keyed[(4, 4)], Color::WHITE.to_u32());
+        let opaque = blitted(&Blit {
+            transparency: Transparency::Opaque,
+            ..Default::default()
+        });
+        assert_eq!(opaque[(1, 4)], Color::BLUE.to_u32());
+        assert_eq!(opaque[(4, 4)], Color::WHITE.to_u32());
+    }
+
+    #[test]
+    fn blit_source_region_clip_and_fade() {
+        let mut buffer = blitted(&Blit {
+            source: Some(Rect::new(1, 0, 1, 1)),
+            ..Default::default()
+        });
+        assert!([(1, 1), (4, 4)].iter().all(|&p| buffer[p] == Color::GREEN.to_u32()));
+        buffer.push_clip(Rect::new(0, 0, 2, 6));
+        buffer.set_alpha(128);
+        buffer.blit(&checker(), Rect::new(0, 0, 6, 6), &Blit::default());
+        assert_eq!(buffer[(0, 0)], Color::rgb(191, 63, 63).to_u32());
+        assert_eq!(buffer[(1, 1)], Color::rgb(128, 127, 0).to_u32());
+        assert_eq!(buffer[(2, 1)], Color::GREEN.to_u32());
+    }
+
+    #[test]
+    fn swap_colors_keeps_alpha() {
+        let sprite = checker().swap_colors(&[(Color::RED, Color::YELLOW), (Color::WHITE, Color::BLACK)]);
+        assert_eq!(sprite.get(0, 0), Color::YELLOW);
+        assert_eq!(sprite.get(1, 0), Color::GREEN);
+        assert_eq!(sprite.get(1, 1), Color::BLACK.with_alpha(0));
+    }
+}
@@ -0,0 +1,437 @@
+#![allow(unused)]
+
+use crate::buffer::{self, Buffer};
+use crate::clip::Rect;
+use crate::linalg::vec2::Vec2;
+
+/// Shape drawn where two segments of a thick stroke meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// Extends the outer edges until they meet, falling back to a bevel when
+    /// the point would be further than `miter_limit` half-widths away.
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Shape drawn at the open ends of a thick stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// Ends exactly at the end point.
+    #[default]
+    Butt,
+    Round,
+    /// Extends past the end point by half the width.
+    Square,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    /// Line width in pixels.
+    pub width: f32,
+    /// Wu lines for thin strokes, 4x4 supersampled coverage for thick ones.
+    pub antialias: bool,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Limit on the miter length as a multiple of half the width.
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths in pixels, starting with a dash.
+    /// Empty draws a solid line; an odd count is repeated to make it even.
+    pub dash: Vec<f32>,
+    /// Distance into the dash pattern at which the stroke starts.
+    pub dash_offset: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            antialias: false,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+            dash: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+impl StrokeStyle {
+    /// A solid, aliased one pixel line, drawn with plain Bresenham.
+    pub fn is_plain(&self) -> bool {
+        self.width <= 1.0 && !self.antialias && !self.is_dashed()
+    }
+
+    pub fn is_dashed(&self) -> bool {
+        self.dash.iter().any(|&length| length > 0.0)
+    }
+}
+
+/// Splits a polyline into the pieces covered by the dashes of `pattern`.
+/// Every piece is open, even if the polyline was closed.
+pub fn dash(points: &[(f32, f32)], closed: bool, pattern: &[f32], offset: f32) -> Vec<Vec<(f32, f32)>> {
+    let mut pattern: Vec<f32> = pattern.iter().map(|&length| length.max(0.0)).collect();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let total: f32 = pattern.iter().sum();
+    let mut points = points.to_vec();
+    if closed && points.len() > 2 {
+        points.push(points[0]);
+    }
+    if total <= 0.0 || points.len() < 2 {
+        return vec![points];
+    }
+
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let mut phase = offset.rem_euclid(total);
+    while phase > remaining {
+        phase -= remaining;
+        index = (index + 1) % pattern.len();
+        remaining = pattern[index];
+    }
+    remaining -= phase;
+
+    let mut pieces = Vec::new();
+    let mut current = if index % 2 == 0 { vec![points[0]] } else { Vec::new() };
+    for pair in points.windows(2) {
+        let (a, b) = (Vec2::from(pair[0]), Vec2::from(pair[1]));
+        let length = a.distance(b);
+        let mut t = 0.0;
+        loop {
+            let step = remaining.min(length - t);
+            t += step;
+            remaining -= step;
+            let p = a.lerp(b, if length > 0.0 { t / length } else { 1.0 }).into();
+            if index % 2 == 0 {
+                current.push(p);
+            }
+            if remaining > 0.0 {
+                break;
+            }
+            if index % 2 == 0 {
+                pieces.push(std::mem::take(&mut current));
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+            if index % 2 == 0 {
+                current.push(p);
+            }
+            if t >= length && remaining > 0.0 {
+                break;
+            }
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// A regular polygon approximating a circle, fine enough that the facets
+/// are no longer than about a pixel.
+pub fn circle_points(center: Vec2<f32>, radius: f32) -> Vec<(f32, f32)> {
+    let segments = (radius * std::f32::consts::TAU).ceil().clamp(8.0, 256.0) as usize;
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            (center + Vec2::new(angle.cos(), angle.sin()) * radius).into()
+        })
+        .collect()
+}
+
+/// Polygons covering a thick open or closed polyline: one quad per segment
+/// plus the joins and caps.
+fn outline(points: &[(f32, f32)], closed: bool, style: &StrokeStyle) -> Vec<Vec<(f32, f32)>> {
+    let half = style.width / 2.0;
+    let mut points: Vec<Vec2<f32>> = points.iter().map(|&p| Vec2::from(p)).collect();
+    points.dedup();
+    if closed && points.len() > 2 && points.first() == points.last() {
+        points.pop();
+    }
+    let closed = closed && points.len() > 2;
+
+    let mut polygons = Vec::new();
+    if points.len() == 1 {
+        let p = points[0];
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Round => polygons.push(circle_points(p, half)),
+            LineCap::Square => polygons.push(
+                [(-half, -half), (half, -half), (half, half), (-half, half)]
+                    .map(|offset| (p + Vec2::from(offset)).into())
+                    .to_vec(),
+            ),
+        }
+        return polygons;
+    }
+
+    let count = if closed { points.len() } else { points.len() - 1 };
+    let segment = |i: usize| (points[i], points[(i + 1) % points.len()]);
+    let mut ends: Vec<(Vec2<f32>, Vec2<f32>)> = (0..count).map(segment).collect();
+    if !closed && style.cap == LineCap::Square {
+        let first = &mut ends[0];
+        first.0 -= (first.1 - first.0).normalize() * half;
+        let last = &mut ends[count - 1];
+        last.1 += (last.1 - last.0).normalize() * half;
+    }
+    for &(a, b) in &ends {
+        let normal = (b - a).normalize().perp() * half;
+        polygons.push([a + normal, b + normal, b - normal, a - normal].map(Into::into).to_vec());
+    }
+
+    if !closed && style.cap == LineCap::Round {
+        polygons.push(circle_points(points[0], half));
+        polygons.push(circle_points(points[points.len() - 1], half));
+    }
+
+    let joins = if closed { 0..count } else { 1..count };
+    for i in joins {
+        let (previous, _) = segment((i + count - 1) % count);
+        let (vertex, next) = segment(i);
+        let d0 = (vertex - previous).normalize();
+        let d1 = (next - vertex).normalize();
+        let turn = d0.cross(d1);
+        if turn == 0.0 && d0.dot(d1) > 0.0 {
+            continue;
+        }
+        if style.join == LineJoin::Round {
+            polygons.push(circle_points(vertex, half));
+            continue;
+        }
+        // The outer side of the corner is where the segment edges part
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let o0 = d0.perp() * side;
+        let o1 = d1.perp() * side;
+        let bevel = [vertex, vertex + o0 * half, vertex + o1 * half];
+        let bisector = (o0 + o1).normalize();
+        let cos = bisector.dot(o0);
+        if style.join == LineJoin::Miter && cos > 0.0 && 1.0 / cos <= style.miter_limit {
+            let tip = vertex + bisector * (half / cos);
+            polygons.push([bevel[0], bevel[1], tip, bevel[2]].map(Into::into).to_vec());
+        } else {
+            polygons.push(bevel.map(Into::into).to_vec());
+        }
+    }
+    polygons
+}
+
+impl Buffer {
+    /// Xiaolin Wu's anti-aliased line between two points in pixel units,
+    /// where pixel `(x, y)` covers `[x, x + 1) x [y, y + 1)`. Each pixel is
+    /// blended with the current color in proportion to its coverage.
+    pub fn line_aa(&mut self, p1: (f32, f32), p2: (f32, f32)) {
+        // Move pixel centers onto integer coordinates
+        let (mut x0, mut y0, mut x1, mut y1) = (p1.0 - 0.5, p1.1 - 0.5, p2.0 - 0.5, p2.1 - 0.5);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+        let gradient = if x1 == x0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+        let plot = |buffer: &mut Self, x: i32, y: i32, coverage: f32| {
+            let point = if steep { (y, x) } else { (x, y) };
+            buffer.pixel_coverage(point, coverage);
+        };
+        let fract = |v: f32| v - v.floor();
+
+        // End points cover only part of their pixel column
+        let endpoint = |buffer: &mut Self, x: f32, y: f32, gap: f32| -> (i32, f32) {
+            let x_end = x.round();
+            let y_end = y + gradient * (x_end - x);
+            let column = x_end as i32;
+            let row = y_end.floor() as i32;
+            plot(buffer, column, row, (1.0 - fract(y_end)) * gap);
+            plot(buffer, column, row.saturating_add(1), fract(y_end) * gap);
+            (column, y_end)
+        };
+        let (start, y_start) = endpoint(self, x0, y0, 1.0 - fract(x0 + 0.5));
+        let (end, _) = endpoint(self, x1, y1, fract(x1 + 0.5));
+
+        // Only the columns inside the clip are walked, each placed from the
+        // start of the whole line so clipping never moves a pixel
+        let rect = self.clip_rect();
+        let (low, high) = if steep { (rect.y, rect.bottom()) } else { (rect.x, rect.right()) };
+        for x in start.saturating_add(1).max(low)..end.min(high) {
+            let y = y_start + gradient * (x as i64 - start as i64) as f32;
+            let row = y.floor() as i32;
+            plot(self, x, row, 1.0 - fract(y));
+            plot(self, x, row.saturating_add(1), fract(y));
+        }
+    }
+
+    /// Strokes a polyline given in pixel units (see `line_aa`), closing it
+    /// back to the first point when `closed` is set.
+    pub fn stroke(&mut self, points: &[(f32, f32)], closed: bool, style: &StrokeStyle) {
+        let (pieces, closed) = if style.is_dashed() {
+            (dash(points, closed, &style.dash, style.dash_offset), false)
+        } else {
+            (vec![points.to_vec()], closed)
+        };
+
+        if style.width <= 1.0 {
+            for piece in &pieces {
+                let count = if closed && piece.len() > 2 { piece.len() } else { piece.len().saturating_sub(1) };
+                for i in 0..count {
+                    let (a, b) = (piece[i], piece[(i + 1) % piece.len()]);
+                    if style.antialias {
+                        self.line_aa(a, b);
+                    } else {
+                        let pixel = |(x, y): (f32, f32)| (x.floor() as i32, y.floor() as i32);
+                        self.line(pixel(a), pixel(b));
+                    }
+                }
+            }
+            return;
+        }
+
+        let polygons: Vec<_> = pieces.iter().flat_map(|piece| outline(piece, closed, style)).collect();
+        self.fill_coverage(&polygons, if style.antialias { 4 } else { 1 });
+    }
+
+    /// Fills the union of `polygons`, so overlapping parts are only blended
+    /// once. Coverage is measured with `samples` x `samples` points per pixel.
+    fn fill_coverage(&mut self, polygons: &[Vec<(f32, f32)>], samples: i32) {
+        let all = polygons.iter().flatten();
+        let min_x = all.clone().map(|p| p.0).fold(f32::INFINITY, f32::min);
+        let max_x = all.clone().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = all.clone().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let max_y = all.map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        if !(min_x <= max_x && min_y <= max_y) {
+            return;
+        }
+        let bounds = Rect::from_corners(
+            (min_x.floor() as i32, min_y.floor() as i32),
+            (max_x.ceil() as i32, max_y.ceil() as i32),
+        );
+        let area = self.clip_rect().intersect(&bounds);
+        if area.is_empty() {
+            return;
+        }
+
+        // Rasterize at the sample resolution, keeping only the spans of each
+        // sample row inside the area. The points are only scaled, by a power
+        // of two, so every sample lands the same wherever the clip is.
+        let scaled = Rect::new(area.x * samples, area.y * samples, area.width * samples, area.height * samples);
+        let mut rows = vec![Vec::new(); scaled.height as usize];
+        for polygon in polygons {
+            let points: Vec<_> = polygon.iter().map(|&(x, y)| (x * samples as f32, y * samples as f32)).collect();
+            buffer::scan_polygon(&scaled, &points, |y, start, end| {
+                rows[(y - scaled.y) as usize].push((start - scaled.x, end - scaled.x))
+            });
+        }
+
+        let total = (samples * samples) as f32;
+        let mut covered = vec![0; area.width as usize];
+        for (y, sample_rows) in rows.chunks_mut(samples as usize).enumerate() {
+            covered.fill(0);
+            for spans in sample_rows {
+                // Count the union of the spans, so overlaps only count once
+                spans.sort_unstable();
+                let mut reached = 0;
+                for &(start, end) in spans.iter() {
+                    for sample in start.max(reached)..end {
+                        covered[(sample / samples) as usize] += 1;
+                    }
+                    reached = reached.max(end);
+                }
+            }
+            for (x, &count) in covered.iter().enumerate() {
+                if count > 0 {
+                    self.pixel_coverage((area.x + x as i32, area.y + y as i32), count as f32 / total);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::Color;
+
+    fn stroked(points: &[(f32, f32)], style: &StrokeStyle) -> Buffer {
+        let mut buffer = Buffer::new(16, 16);
+        buffer.stroke(points, false, style);
+        buffer
+    }
+
+    fn thick(width: f32) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            ..Default::default()
+        }
+    }
+
+    fn lit_count(buffer: &Buffer) -> usize {
+        buffer.iter().filter(|&&p| p != 0).count()
+    }
+
+    #[test]
+    fn dashes_split_the_line() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        let starts = |pattern: &[f32], offset: f32| -> Vec<(f32, f32)> {
+            dash(&line, false, pattern, offset).iter().map(|piece| (piece[0].0, piece[piece.len() - 1].0)).collect()
+        };
+        assert_eq!(starts(&[3.0, 2.0], 0.0), [(0.0, 3.0), (5.0, 8.0)]);
+        assert_eq!(starts(&[3.0, 2.0], 1.0), [(0.0, 2.0), (4.0, 7.0), (9.0, 10.0)]);
+        assert_eq!(starts(&[2.0], 0.0), [(0.0, 2.0), (4.0, 6.0), (8.0, 10.0)]);
+        assert_eq!(dash(&line, false, &[0.0, 0.0], 0.0), [line.to_vec()]);
+    }
+
+    #[test]
+    fn caps_extend_the_ends() {
+        let line = [(2.0, 5.0), (12.0, 5.0)];
+        let butt = stroked(&line, &thick(4.0));
+        assert_eq!(lit_count(&butt), 10 * 4);
+        assert!(butt[(2, 3)] != 0 && butt[(11, 6)] != 0 && butt[(1, 5)] == 0);
+        let square = stroked(&line, &StrokeStyle { cap: LineCap::Square, ..thick(4.0) });
+        assert_eq!(lit_count(&square), 14 * 4);
+        let round = stroked(&line, &StrokeStyle { cap: LineCap::Round, ..thick(4.0) });
+        assert!(round[(1, 5)] != 0 && round[(0, 3)] == 0);
+    }
+
+    #[test]
+    fn joins_fill_the_outer_corner() {
+        let corner = [(2.0, 10.0), (10.0, 10.0), (10.0, 2.0)];
+        let lit = |join, miter_limit| {
+            let buffer = stroked(&corner, &StrokeStyle { join, miter_limit, ..thick(6.0) });
+            [(12, 12), (11, 12), (10, 10)].map(|p| buffer[p] != 0)
+        };
+        assert_eq!(lit(LineJoin::Miter, 4.0), [true, true, true]);
+        assert_eq!(lit(LineJoin::Miter, 1.0), [false, false, true]);
+        assert_eq!(lit(LineJoin::Round, 4.0), [false, true, true]);
+        assert_eq!(lit(LineJoin::Bevel, 4.0), [false, false, true]);
+    }
+
+    #[test]
+    fn overlaps_at_a_join_are_blended_once() {
+        let mut buffer = Buffer::new(16, 16);
+        buffer.set_alpha(128);
+        buffer.stroke(&[(2.0, 10.0), (10.0, 10.0), (10.0, 2.0)], false, &thick(6.0));
+        let segment = buffer[(4, 10)];
+        assert_eq!(segment, Color::rgb(128, 128, 128).to_u32());
+        assert_eq!(buffer[(10, 10)], segment);
+        assert_eq!(buffer[(9, 9)], segment);
+    }
+
+    #[test]
+    fn antialiased_lines_split_coverage() {
+        let mut buffer = Buffer::new(10, 10);
+        buffer.line_aa((1.5, 5.0), (8.5, 5.0));
+        assert_eq!((buffer[(4, 4)], buffer[(4, 5)]), (0x808080, 0x808080));
+        assert_eq!((buffer[(4, 3)], buffer[(4, 6)]), (0, 0));
+        let style = StrokeStyle {
+            antialias: true,
+            ..thick(2.0)
+        };
+        let buffer = stroked(&[(2.0, 5.5), (12.0, 5.5)], &style);
+        assert_eq!((buffer[(6, 5)], buffer[(6, 4)], buffer[(6, 3)]), (0xFFFFFF, 0x808080, 0));
+    }
+}
@@ -0,0 +1,168 @@
+#![allow(unused)]
+
+use crate::colors::Color;
+use crate::grid::{Cell, CollisionGrid};
+use crate::tilemap::{TileLook, Tilemap};
+
+/// Colors for each semantic role in the `ty` game, so the whole look can be
+/// swapped at once instead of editing every draw call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Replaces the player sprite's fill color.
+    pub player: Color,
+    pub terrain: Color,
+    pub goal: Color,
+    /// Anything that kills the player, like the side walls.
+    pub hazard: Color,
+    pub background: Color,
+    pub text: Color,
+    /// Pop-up messages such as "victory".
+    pub highlight: Color,
+}
+
+impl Theme {
+    /// The original look.
+    pub const CLASSIC: Theme = Theme {
+        name: "classic",
+        player: Color::from_u32(0x4D6DF3),
+        terrain: Color::WHITE,
+        goal: Color::BLUE,
+        hazard: Color::RED,
+        background: Color::BLACK,
+        text: Color::WHITE,
+        highlight: Color::GREEN,
+    };
+
+    /// Avoids red/green contrasts, which look alike without green cones.
+    /// Roles differ in both hue (blue/orange) and lightness.
+    pub const DEUTERANOPIA: Theme = Theme {
+        name: "deuteranopia",
+        player: Color::from_u32(0xF0E442),
+        terrain: Color::from_u32(0xBBBBBB),
+        goal: Color::from_u32(0x56B4E9),
+        hazard: Color::from_u32(0xD55E00),
+        background: Color::BLACK,
+        text: Color::WHITE,
+        highlight: Color::from_u32(0xF0E442),
+    };
+
+    /// Without red cones reds turn dark, so the hazard is a bright orange.
+    pub const PROTANOPIA: Theme = Theme {
+        name: "protanopia",
+        player: Color::WHITE,
+        terrain: Color::from_u32(0x999999),
+        goal: Color::from_u32(0x56B4E9),
+        hazard: Color::from_u32(0xE69F00),
+        background: Color::BLACK,
+        text: Color::WHITE,
+        highlight: Color::from_u32(0xF0E442),
+    };
+
+    /// Avoids blue/yellow contrasts, relying on red against teal instead.
+    pub const TRITANOPIA: Theme = Theme {
+        name: "tritanopia",
+        player: Color::WHITE,
+        terrain: Color::from_u32(0x999999),
+        goal: Color::from_u32(0x2CC9B8),
+        hazard: Color::from_u32(0xF05A5A),
+        background: Color::BLACK,
+        text: Color::WHITE,
+        highlight: Color::from_u32(0xF5A3C7),
+    };
+
+    /// Fully saturated colors at maximum lightness on black.
+    pub const HIGH_CONTRAST: Theme = Theme {
+        name: "high-contrast",
+        player: Color::CYAN,
+        terrain: Color::WHITE,
+        goal: Color::YELLOW,
+        hazard: Color::MAGENTA,
+        background: Color::BLACK,
+        text: Color::WHITE,
+        highlight: Color::YELLOW,
+    };
+
+    pub const ALL: &'static [Theme] = &[
+        Theme::CLASSIC,
+        Theme::DEUTERANOPIA,
+        Theme::PROTANOPIA,
+        Theme::TRITANOPIA,
+        Theme::HIGH_CONTRAST,
+    ];
+
+    /// Looks up a built-in theme, ignoring case.
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Self::ALL.iter().copied().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The built-in theme after this one, wrapping around.
+    pub fn next(&self) -> Theme {
+        let index = Self::ALL.iter().position(|t| t == self).map_or(0, |i| i + 1);
+        Self::ALL[index % Self::ALL.len()]
+    }
+
+    /// The terrain of `grid` as one unit-sized tile per cell: solid cells in
+    /// the terrain color, deadly ones a faint hazard color, empty ones left
+    /// undrawn.
+    pub fn terrain_tiles(&self, grid: &CollisionGrid) -> Tilemap {
+        let mut map = Tilemap::from_fn(grid.width(), grid.height(), 1.0, |x, y| match grid.get((x as isize, y as isize)) {
+            Cell::Empty => 0,
+            Cell::Solid => 1,
+            Cell::Kill => 2,
+        });
+        map.set_look(1, TileLook::Color(self.terrain));
+        map.set_look(2, TileLook::Color(self.hazard.with_alpha(96)));
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite::Sprite;
+
+    #[test]
+    fn themes_are_found_by_name() {
+        assert_eq!(Theme::by_name("High-Contrast"), Some(Theme::HIGH_CONTRAST));
+        assert_eq!(Theme::by_name("sepia"), None);
+        for theme in Theme::ALL {
+            assert_eq!(Theme::by_name(theme.name), Some(*theme));
+        }
+    }
+
+    #[test]
+    fn next_cycles_through_every_theme() {
+        let mut theme = Theme::CLASSIC;
+        let mut seen = Vec::new();
+        for _ in 0..Theme::ALL.len() {
+            seen.push(theme.name);
+            theme = theme.next();
+        }
+        assert_eq!(theme, Theme::CLASSIC);
+        assert_eq!(seen, Theme::ALL.iter().map(|t| t.name).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn roles_stand_out() {
+        for theme in Theme::ALL {
+            let roles = [theme.player, theme.terrain, theme.goal, theme.hazard, theme.text, theme.highlight];
+            assert!(roles.iter().all(|&role| role != theme.background), "{}", theme.name);
+            let board = [theme.terrain, theme.goal, theme.hazard];
+            assert!(board[0] != board[1] && board[1] != board[2] && board[0] != board[2], "{}", theme.name);
+        }
+    }
+
+    #[test]
+    fn player_sprite_takes_the_theme_color() {
+        let sheet = Sprite::load("assets/pixil-frame-0.png").unwrap();
+        let fill = |sprite: &Sprite, color: Color| {
+            sprite.pixels().iter().filter(|&&p| Color::from_argb(p).to_u32() == color.to_u32() && p >> 24 != 0).count()
+        };
+        let original = fill(&sheet, Theme::CLASSIC.player);
+        assert!(original > 0);
+        let recolored = sheet.swap_colors(&[(Theme::CLASSIC.player, Theme::DEUTERANOPIA.player)]);
+        assert_eq!(fill(&recolored, Theme::DEUTERANOPIA.player), original);
+        assert_eq!(fill(&recolored, Theme::CLASSIC.player), 0);
+    }
+}
@@ -0,0 +1,180 @@
+#![allow(unused)]
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::colors::Color;
+use crate::sprite::Sprite;
+
+/// How tiles with a given id are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileLook {
+    Empty,
+    Color(Color),
+    /// Index into the tilemap's sprites, see `Tilemap::add_sprite`.
+    Sprite(usize),
+}
+
+/// A grid of tile ids in world space. Tile `(0, 0)` sits at the origin and
+/// columns grow to the right, rows upwards. Id 0 is empty unless given a
+/// look.
+pub struct Tilemap {
+    columns: usize,
+    rows: usize,
+    /// Edge length of a tile in world units.
+    pub tile_size: f32,
+    /// World position of the bottom-left corner of tile `(0, 0)`.
+    pub origin: (f32, f32),
+    tiles: Vec<u16>,
+    looks: Vec<TileLook>,
+    sprites: Vec<Rc<Sprite>>,
+}
+
+impl Tilemap {
+    pub fn new(columns: usize, rows: usize, tile_size: f32) -> Self {
+        Self::from_fn(columns, rows, tile_size, |_, _| 0)
+    }
+
+    /// Builds the map from `tile(column, row)`, e.g. straight from a
+    /// collision grid.
+    pub fn from_fn(columns: usize, rows: usize, tile_size: f32, mut tile: impl FnMut(usize, usize) -> u16) -> Self {
+        let mut tiles = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            tiles.extend((0..columns).map(|column| tile(column, row)));
+        }
+        Self {
+            columns,
+            rows,
+            tile_size,
+            origin: (0.0, 0.0),
+            tiles,
+            looks: Vec::new(),
+            sprites: Vec::new(),
+        }
+    }
+
+    /// Columns and rows.
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> u16 {
+        self.tiles[row * self.columns + column]
+    }
+
+    pub fn set(&mut self, column: usize, row: usize, id: u16) {
+        self.tiles[row * self.columns + column] = id;
+    }
+
+    pub fn look(&self, id: u16) -> TileLook {
+        self.looks.get(id as usize).copied().unwrap_or(TileLook::Empty)
+    }
+
+    pub fn set_look(&mut self, id: u16, look: TileLook) {
+        let id = id as usize;
+        if id >= self.looks.len() {
+            self.looks.resize(id + 1, TileLook::Empty);
+        }
+        self.looks[id] = look;
+    }
+
+    /// Stores a sprite for `TileLook::Sprite`, returning its index.
+    pub fn add_sprite(&mut self, sprite: impl Into<Rc<Sprite>>) -> usize {
+        self.sprites.push(sprite.into());
+        self.sprites.len() - 1
+    }
+
+    pub fn sprite(&self, index: usize) -> &Rc<Sprite> {
+        &self.sprites[index]
+    }
+
+    /// Bottom-left and top-right world corners of a tile.
+    pub fn tile_bounds(&self, column: usize, row: usize) -> ((f32, f32), (f32, f32)) {
+        let x = self.origin.0 + column as f32 * self.tile_size;
+        let y = self.origin.1 + row as f32 * self.tile_size;
+        ((x, y), (x + self.tile_size, y + self.tile_size))
+    }
+
+    /// The tile containing a world position, if it is inside the map.
+    pub fn tile_at(&self, (x, y): (f32, f32)) -> Option<(usize, usize)> {
+        let column = ((x - self.origin.0) / self.tile_size).floor();
+        let row = ((y - self.origin.1) / self.tile_size).floor();
+        let inside = column >= 0.0 && row >= 0.0 && (column as usize) < self.columns && (row as usize) < self.rows;
+        inside.then_some((column as usize, row as usize))
+    }
+
+    /// Columns and rows of the tiles overlapping a world rectangle.
+    pub fn visible_range(&self, min: (f32, f32), max: (f32, f32)) -> (Range<usize>, Range<usize>) {
+        let range = |min: f32, max: f32, origin: f32, count: usize| {
+            let start = ((min - origin) / self.tile_size).floor().max(0.0) as usize;
+            let end = ((max - origin) / self.tile_size).ceil().max(0.0) as usize;
+            start.min(count)..end.min(count)
+        };
+        (
+            range(min.0, max.0, self.origin.0, self.columns),
+            range(min.1, max.1, self.origin.1, self.rows),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera2D;
+    use crate::canvas::Canvas;
+    use crate::linalg::vec2::Vec2;
+
+    fn map() -> Tilemap {
+        let mut map = Tilemap::from_fn(4, 3, 2.0, |column, row| (column == row) as u16);
+        map.origin = (-1.0, 1.0);
+        map
+    }
+
+    #[test]
+    fn world_positions_map_to_tiles() {
+        let map = map();
+        assert_eq!((map.size(), map.get(1, 1), map.get(2, 1)), ((4, 3), 1, 0));
+        assert_eq!(map.tile_bounds(2, 1), ((3.0, 3.0), (5.0, 5.0)));
+        assert_eq!(map.tile_at((3.0, 3.0)), Some((2, 1)));
+        assert_eq!(map.tile_at((6.99, 6.99)), Some((3, 2)));
+        assert_eq!(map.tile_at((7.0, 3.0)), None);
+        assert_eq!(map.tile_at((-1.5, 3.0)), None);
+        assert_eq!(map.visible_range((0.0, 2.5), (3.5, 100.0)), (0..3, 0..3));
+        assert_eq!(map.visible_range((-50.0, -50.0), (-10.0, -10.0)), (0..0, 0..0));
+    }
+
+    #[test]
+    fn looks_default_to_empty() {
+        let mut map = map();
+        assert_eq!(map.look(1), TileLook::Empty);
+        map.set_look(3, TileLook::Color(Color::RED));
+        assert_eq!((map.look(2), map.look(3)), (TileLook::Empty, TileLook::Color(Color::RED)));
+        map.set(0, 0, 3);
+        assert_eq!(map.get(0, 0), 3);
+    }
+
+    #[test]
+    fn draws_tiles_where_the_camera_sees_them() {
+        // Two pixels per tile, with tile (0, 0) at the bottom left
+        let mut camera = Camera2D::new(4.0, 4.0);
+        camera.position = Vec2::new(2.0, 2.0);
+        let mut canvas = Canvas::new(8, 8);
+        canvas.set_camera(Some(camera));
+        canvas.set_color(Color::BLUE);
+        let mut map = Tilemap::from_fn(4, 4, 1.0, |column, row| match (column, row) {
+            (0, 0) => 1,
+            (3, 3) => 2,
+            _ => 0,
+        });
+        map.set_look(1, TileLook::Color(Color::RED));
+        let sprite = map.add_sprite(Sprite::from_pixels(1, 1, vec![Color::GREEN.to_argb()]));
+        map.set_look(2, TileLook::Sprite(sprite));
+        canvas.draw_tilemap(&map);
+        let pixel = |(x, y): (usize, usize)| canvas.buffer()[y * 8 + x];
+        assert!([(0, 6), (1, 6), (0, 7), (1, 7)].iter().all(|&p| pixel(p) == 0xFF0000));
+        assert!([(6, 0), (7, 1)].iter().all(|&p| pixel(p) == 0x00FF00));
+        assert!([(2, 6), (0, 5), (4, 4), (5, 1)].iter().all(|&p| pixel(p) == 0));
+        canvas.fill();
+        assert!(canvas.buffer().iter().all(|&p| p == 0x0000FF));
+    }
+}
@@ -0,0 +1,287 @@
+#![allow(unused)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::blend::BlendMode;
+use crate::buffer::Buffer;
+use crate::clip::Rect;
+use crate::colors::Color;
+use crate::linalg::vec2::Vec2;
+use crate::raster::ScreenVertex;
+use crate::sprite::{Blit, Sprite};
+
+enum Shape<'a> {
+    Polygon(Vec<(f32, f32)>),
+    Triangle([ScreenVertex; 3], Option<&'a Sprite>),
+    Blit(&'a Sprite, Rect, Blit),
+}
+
+struct Command<'a> {
+    shape: Shape<'a>,
+    color: Color,
+    blend_mode: BlendMode,
+    /// Pixels the shape can touch, used for binning.
+    bounds: Rect,
+}
+
+/// Records draw commands and renders them in parallel. The target is split
+/// into square tiles, each command is binned into the tiles its bounds
+/// overlap, and worker threads render whole tiles into private buffers
+/// that are copied back afterwards. Commands keep their order within a
+/// tile, so the result matches drawing them one by one on the target, up to
+/// rounding at triangle edges from shifting them into tile coordinates.
+pub struct TileRenderer<'a> {
+    /// Edge length of a tile in pixels.
+    pub tile_size: usize,
+    /// Worker threads to use, defaulting to the available parallelism.
+    pub threads: usize,
+    color: Color,
+    blend_mode: BlendMode,
+    commands: Vec<Command<'a>>,
+}
+
+impl<'a> Default for TileRenderer<'a> {
+    fn default() -> Self {
+        Self {
+            tile_size: 64,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            color: Color::WHITE,
+            blend_mode: BlendMode::Normal,
+            commands: Vec::new(),
+        }
+    }
+}
+
+/// Pixels touched by points spread `margin` around their bounding box.
+fn point_bounds(points: impl Iterator<Item = (f32, f32)> + Clone, margin: f32) -> Rect {
+    let min_x = points.clone().map(|p| p.0).fold(f32::INFINITY, f32::min);
+    let max_x = points.clone().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+    let min_y = points.clone().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = points.map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+    if !(min_x <= max_x && min_y <= max_y) {
+        return Rect::new(0, 0, 0, 0);
+    }
+    let (x, y) = ((min_x - margin).floor() as i32, (min_y - margin).floor() as i32);
+    let (right, bottom) = ((max_x + margin).ceil() as i32, (max_y + margin).ceil() as i32);
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+impl<'a> TileRenderer<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_color(&mut self, color: impl Into<Color>) {
+        self.color = color.into();
+    }
+
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    /// Number of recorded commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Drops the recorded commands, keeping the color and blend mode.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    fn record(&mut self, shape: Shape<'a>, bounds: Rect) {
+        self.commands.push(Command {
+            shape,
+            color: self.color,
+            blend_mode: self.blend_mode,
+            bounds,
+        });
+    }
+
+    /// Records `Buffer::fill_polygon_f32`.
+    pub fn fill_polygon_f32(&mut self, points: &[(f32, f32)]) {
+        let bounds = point_bounds(points.iter().copied(), 0.0);
+        self.record(Shape::Polygon(points.to_vec()), bounds);
+    }
+
+    /// Records `Buffer::raster_tri`.
+    pub fn raster_tri(&mut self, vertices: [ScreenVertex; 3], texture: Option<&'a Sprite>) {
+        let bounds = point_bounds(vertices.iter().map(|v| (v.position.x, v.position.y)), 1.0);
+        self.record(Shape::Triangle(vertices, texture), bounds);
+    }
+
+    /// Records `Buffer::blit`.
+    pub fn blit(&mut self, sprite: &'a Sprite, dest: Rect, blit: &Blit) {
+        self.record(Shape::Blit(sprite, dest, *blit), dest);
+    }
+
+    /// Draws the recorded commands into `target` within its clip rectangle.
+    /// The commands are kept, so call `clear` before recording the next
+    /// frame.
+    pub fn render(&self, target: &mut Buffer) {
+        let clip = target.clip_rect();
+        let tile_size = self.tile_size.max(1) as i32;
+        let (width, height) = target.size();
+        let columns = (width as i32 + tile_size - 1) / tile_size;
+        let rows = (height as i32 + tile_size - 1) / tile_size;
+        let tile_rect = |index: i32| {
+            let rect = Rect::new(index % columns * tile_size, index / columns * tile_size, tile_size, tile_size);
+            rect.intersect(&clip)
+        };
+
+        let mut bins = vec![Vec::new(); (columns * rows) as usize];
+        for (i, command) in self.commands.iter().enumerate() {
+            let bounds = command.bounds.intersect(&clip);
+            if bounds.is_empty() {
+                continue;
+            }
+            for row in bounds.y / tile_size..=(bounds.bottom() - 1) / tile_size {
+                for column in bounds.x / tile_size..=(bounds.right() - 1) / tile_size {
+                    bins[(row * columns + column) as usize].push(i);
+                }
+            }
+        }
+        let work: Vec<usize> = (0..bins.len()).filter(|&i| !bins[i].is_empty()).collect();
+
+        let next = AtomicUsize::new(0);
+        let threads = self.threads.clamp(1, work.len().max(1));
+        let target_ref = &*target;
+        let finished: Vec<(Rect, Buffer)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let Some(&tile) = work.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                                break done;
+                            };
+                            let rect = tile_rect(tile as i32);
+                            done.push((rect, self.render_tile(target_ref, rect, &bins[tile])));
+                        }
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        });
+        for (rect, tile) in finished {
+            target.copy_rect(&tile, Rect::new(0, 0, rect.width, rect.height), (rect.x, rect.y));
+        }
+    }
+
+    /// Replays the commands in `bin` on a copy of `rect` in `target`, with
+    /// coordinates shifted so the tile's corner is at the origin.
+    fn render_tile(&self, target: &Buffer, rect: Rect, bin: &[usize]) -> Buffer {
+        let mut tile = Buffer::new(rect.width as usize, rect.height as usize);
+        if target.has_depth() {
+            tile.enable_depth();
+        }
+        tile.copy_rect(target, rect, (0, 0));
+        let (dx, dy) = (rect.x as f32, rect.y as f32);
+        for &i in bin {
+            let command = &self.commands[i];
+            tile.set_color(command.color);
+            tile.set_blend_mode(command.blend_mode);
+            match &command.shape {
+                Shape::Polygon(points) => {
+                    let points: Vec<_> = points.iter().map(|&(x, y)| (x - dx, y - dy)).collect();
+                    tile.fill_polygon_f32(&points);
+                }
+                Shape::Triangle(vertices, texture) => {
+                    let offset = Vec2::new(dx, dy);
+                    let vertices = vertices.map(|v| ScreenVertex { position: v.position - offset, ..v });
+                    tile.raster_tri(vertices, *texture);
+                }
+                Shape::Blit(sprite, dest, blit) => {
+                    let dest = Rect::new(dest.x - rect.x, dest.y - rect.y, dest.width, dest.height);
+                    tile.blit(sprite, dest, blit);
+                }
+            }
+        }
+        tile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::vec3::Vec3;
+
+    fn vertex(x: f32, y: f32, depth: f32, color: Vec3<f32>) -> ScreenVertex {
+        ScreenVertex {
+            position: Vec2::new(x, y),
+            depth,
+            inv_w: 1.0,
+            color,
+            uv: Vec2::zero(),
+        }
+    }
+
+    fn target() -> Buffer {
+        let mut buffer = Buffer::new(100, 70);
+        buffer.enable_depth();
+        buffer
+    }
+
+    #[test]
+    fn tiled_rendering_matches_drawing_directly() {
+        let checker = Sprite::from_pixels(2, 2, vec![0xFFFF0000, 0x8000FF00, 0, 0xFF0000FF]);
+        let background = [(0.0, 0.0), (100.0, 0.0), (100.0, 70.0), (0.0, 70.0)];
+        let wedge = [(50.0, 37.0), (98.0, 42.5), (85.0, 68.0)];
+        let near = [(10.0, 10.0), (80.0, 20.0), (30.0, 60.0)].map(|(x, y)| vertex(x, y, 0.2, Vec3::new(1.0, 1.0, 0.0)));
+        let far = [(0.0, 30.0), (95.0, 5.0), (60.0, 69.0)].map(|(x, y)| vertex(x, y, 0.7, Vec3::new(0.0, 1.0, 1.0)));
+        let dest = Rect::new(60, 40, 37, 29);
+
+        let mut direct = target();
+        direct.set_color(Color::GRAY);
+        direct.fill_polygon_f32(&background);
+        direct.raster_tri(near, None);
+        direct.raster_tri(far, None);
+        direct.set_color(Color::RED.with_alpha(100));
+        direct.fill_polygon_f32(&wedge);
+        direct.blit(&checker, dest, &Blit::default());
+        direct.set_blend_mode(BlendMode::Additive);
+        direct.fill_polygon_f32(&wedge.map(|(x, y)| (x + 3.0, y - 4.0)));
+
+        let mut tiles = TileRenderer {
+            tile_size: 16,
+            threads: 3,
+            ..Default::default()
+        };
+        tiles.set_color(Color::GRAY);
+        tiles.fill_polygon_f32(&background);
+        tiles.raster_tri(near, None);
+        tiles.raster_tri(far, None);
+        tiles.set_color(Color::RED.with_alpha(100));
+        tiles.fill_polygon_f32(&wedge);
+        tiles.blit(&checker, dest, &Blit::default());
+        tiles.set_blend_mode(BlendMode::Additive);
+        tiles.fill_polygon_f32(&wedge.map(|(x, y)| (x + 3.0, y - 4.0)));
+        assert_eq!(tiles.len(), 6);
+        let mut tiled = target();
+        tiles.render(&mut tiled);
+
+        assert!(*tiled == *direct, "tiled rendering differs");
+        // The far triangle is drawn last but stays behind the near one
+        assert_eq!(tiled[(30, 30)], 0xFFFF00);
+    }
+
+    #[test]
+    fn rendering_stays_inside_the_clip() {
+        let mut tiles = TileRenderer::new();
+        tiles.tile_size = 8;
+        tiles.fill_polygon_f32(&[(0.0, 0.0), (100.0, 0.0), (100.0, 70.0), (0.0, 70.0)]);
+        let mut buffer = target();
+        buffer.push_clip(Rect::new(5, 6, 20, 10));
+        tiles.render(&mut buffer);
+        let lit: Vec<_> = (0..70).flat_map(|y| (0..100).map(move |x| (x, y))).filter(|&p| buffer[p] != 0).collect();
+        assert_eq!(lit.len(), 20 * 10);
+        assert!(lit.iter().all(|&(x, y)| (5..25).contains(&x) && (6..16).contains(&y)));
+        tiles.clear();
+        assert!(tiles.is_empty());
+    }
+}
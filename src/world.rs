@@ -0,0 +1,364 @@
+#![allow(unused)]
+
+use std::cmp::max;
+
+use crate::grid::CollisionGrid;
+use crate::level::Level;
+
+/// Buttons held during one step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub jump: bool,
+}
+
+impl Input {
+    /// -1 for left, 1 for right and 0 for neither; left wins if both are
+    /// held.
+    pub fn direction(&self) -> isize {
+        if self.left {
+            -1
+        } else if self.right {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// What happened during one step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Events {
+    /// The player entered a deadly cell and was sent back to the spawn.
+    pub died: bool,
+    /// The player reached the goal.
+    pub won: bool,
+    /// A new level started after the goal was reached. Not set when the
+    /// last level was won.
+    pub level_changed: bool,
+}
+
+/// The `ty` game's rules on a grid of cells, one cell per step, without any
+/// input handling or drawing.
+pub struct GridWorld {
+    levels: Vec<Level>,
+    /// Index into `levels`, equal to its length once all are beaten.
+    level: usize,
+    player: (isize, isize),
+    /// Rows moved up at the start of the next step, negative when falling.
+    up_velocity: isize,
+    /// Direction of the last step's input.
+    direction: isize,
+    collisions: CollisionGrid,
+    /// Steps since the level (re)started, which moves its platforms.
+    platform_tick: usize,
+}
+
+impl GridWorld {
+    /// Starts at the spawn of the first level.
+    pub fn new(levels: Vec<Level>) -> Self {
+        Self::starting_at(levels, 0)
+    }
+
+    /// Starts at the spawn of `levels[level]`.
+    pub fn starting_at(levels: Vec<Level>, level: usize) -> Self {
+        assert!(level < levels.len(), "level {level} out of {} levels", levels.len());
+        let player = levels[level].spawn;
+        let collisions = levels[level].collisions(0);
+        Self {
+            levels,
+            level,
+            player,
+            up_velocity: 0,
+            direction: 0,
+            collisions,
+            platform_tick: 0,
+        }
+    }
+
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    /// Index of the level being played, equal to the number of levels once
+    /// all are beaten.
+    pub fn level_index(&self) -> usize {
+        self.level
+    }
+
+    /// The level being played, or `None` after the last one was beaten.
+    pub fn level(&self) -> Option<&Level> {
+        self.levels.get(self.level)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.level >= self.levels.len()
+    }
+
+    /// Cell of the player, row 0 at the bottom.
+    pub fn player(&self) -> (isize, isize) {
+        self.player
+    }
+
+    pub fn up_velocity(&self) -> isize {
+        self.up_velocity
+    }
+
+    /// -1, 0 or 1 for the horizontal input of the last step.
+    pub fn direction(&self) -> isize {
+        self.direction
+    }
+
+    /// The current level's cells, platforms included. After the last level
+    /// they stay as they were.
+    pub fn collisions(&self) -> &CollisionGrid {
+        &self.collisions
+    }
+
+    fn solid(&self, (column, row): (isize, isize)) -> bool {
+        self.collisions.is_solid(column, row)
+    }
+
+    /// Puts the player back on the current level's spawn and restarts its
+    /// platforms.
+    fn respawn(&mut self) {
+        if let Some(level) = self.level() {
+            self.player = level.spawn;
+            self.up_velocity = 0;
+            self.platform_tick = 0;
+        }
+    }
+
+    /// Advances the game by one step.
+    pub fn step(&mut self, input: Input) -> Events {
+        let mut events = Events::default();
+        self.player.1 += self.up_velocity;
+
+        if self.collisions.is_deadly(self.player.0, self.player.1) {
+            events.died = true;
+            self.respawn();
+        }
+
+        if self.player.0 == 2 {
+            self.up_velocity = 0;
+        }
+
+        self.direction = input.direction();
+        let (x, y) = self.player;
+        if !self.solid((x, y - 1)) {
+            self.up_velocity = max(-1, self.up_velocity - 1);
+        } else if self.level().is_some_and(|level| level.in_lift_shaft(x)) {
+            self.up_velocity = 1;
+        } else {
+            self.up_velocity = 0;
+        }
+
+        if self.solid((x, y)) {
+            self.up_velocity = 0;
+            self.player.1 += 2;
+        }
+
+        let (x, y) = self.player;
+        if self.solid((x, y - 1)) && input.jump {
+            self.up_velocity = 3;
+        }
+
+        if !self.solid((x - 1, y)) && self.direction < 0 {
+            self.player.0 -= 1;
+        }
+
+        let (x, y) = self.player;
+        if !self.solid((x + 1, y)) && self.direction > 0 {
+            self.player.0 += 1;
+        }
+
+        if self.level().is_some_and(|level| level.goal == self.player) {
+            events.won = true;
+            self.level += 1;
+            events.level_changed = !self.is_finished();
+            self.respawn();
+        }
+
+        if let Some(level) = self.level() {
+            self.collisions = level.collisions(self.platform_tick);
+        }
+        self.platform_tick += 1;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Cell;
+
+    /// A 19 by 19 level with a floor on row 1 and deadly cells below it,
+    /// plus the given solid cells and platform lines. Everything outside
+    /// kills.
+    fn level(spawn: (usize, usize), goal: (usize, usize), solid: &[(usize, usize)], platforms: &str) -> Level {
+        let mut rows = vec![vec!['.'; 19]; 19];
+        rows[0].fill('!');
+        rows[1].fill('#');
+        for &(column, row) in solid {
+            rows[row][column] = '#';
+        }
+        rows[spawn.1][spawn.0] = 'S';
+        rows[goal.1][goal.0] = 'G';
+        let map: Vec<String> = rows.iter().rev().map(|row| row.iter().collect()).collect();
+        Level::parse(&format!("{platforms}\nmap\n{}", map.join("\n"))).unwrap()
+    }
+
+    fn run(world: &mut GridWorld, input: Input, steps: usize) -> Events {
+        let mut all = Events::default();
+        for _ in 0..steps {
+            let events = world.step(input);
+            all.died |= events.died;
+            all.won |= events.won;
+            all.level_changed |= events.level_changed;
+        }
+        all
+    }
+
+    const RIGHT: Input = Input {
+        left: false,
+        right: true,
+        jump: false,
+    };
+    const JUMP: Input = Input {
+        left: false,
+        right: false,
+        jump: true,
+    };
+
+    #[test]
+    fn gravity_pulls_down_one_row_per_step_onto_the_floor() {
+        let mut world = GridWorld::new(vec![level((10, 10), (17, 2), &[], "")]);
+        world.step(Input::default());
+        assert_eq!(world.up_velocity(), -1);
+        for row in (2..10).rev() {
+            world.step(Input::default());
+            assert_eq!(world.player(), (10, row));
+        }
+        assert_eq!(world.up_velocity(), 0);
+        run(&mut world, Input::default(), 5);
+        assert_eq!(world.player(), (10, 2));
+    }
+
+    #[test]
+    fn jumping_rises_three_rows_then_falls_back() {
+        let mut world = GridWorld::new(vec![level((10, 2), (17, 2), &[], "")]);
+        world.step(JUMP);
+        assert_eq!(world.up_velocity(), 3);
+        let rows: Vec<isize> = (0..10)
+            .map(|_| {
+                world.step(Input::default());
+                world.player().1
+            })
+            .collect();
+        assert_eq!(rows, [5, 7, 8, 8, 7, 6, 5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn column_2_cuts_a_jump_short() {
+        let mut world = GridWorld::new(vec![level((2, 2), (17, 2), &[], "")]);
+        world.step(JUMP);
+        let rows: Vec<isize> = (0..5)
+            .map(|_| {
+                world.step(Input::default());
+                world.player().1
+            })
+            .collect();
+        assert_eq!(rows, [5, 4, 3, 2, 2]);
+    }
+
+    #[test]
+    fn cannot_jump_in_mid_air() {
+        let mut world = GridWorld::new(vec![level((10, 8), (17, 2), &[], "")]);
+        world.step(JUMP);
+        assert_eq!(world.up_velocity(), -1);
+    }
+
+    #[test]
+    fn walls_block_sideways_movement() {
+        let mut world = GridWorld::new(vec![level((10, 2), (17, 2), &[(12, 2), (12, 3)], "")]);
+        run(&mut world, RIGHT, 5);
+        assert_eq!(world.player(), (11, 2));
+        assert_eq!(world.direction(), 1);
+    }
+
+    #[test]
+    fn leaving_the_field_respawns() {
+        let mut world = GridWorld::new(vec![level((17, 2), (3, 2), &[], "")]);
+        assert!(!world.step(RIGHT).died);
+        let events = world.step(RIGHT);
+        assert!(!events.died);
+        assert_eq!(world.player(), (19, 2));
+        let events = world.step(Input::default());
+        assert!(events.died);
+        assert_eq!(world.player(), (17, 2));
+    }
+
+    #[test]
+    fn deadly_cells_respawn() {
+        let mut source = level((10, 2), (17, 2), &[], "");
+        source.grid.set((12, 2), Cell::Kill);
+        let mut world = GridWorld::new(vec![source]);
+        run(&mut world, RIGHT, 2);
+        assert_eq!(world.player(), (12, 2));
+        assert!(world.step(Input::default()).died);
+        assert_eq!(world.player(), (10, 2));
+    }
+
+    #[test]
+    fn solid_outside_acts_as_a_wall() {
+        let mut source = level((17, 2), (3, 2), &[], "");
+        source.grid.outside = Cell::Solid;
+        let mut world = GridWorld::new(vec![source]);
+        assert!(!run(&mut world, RIGHT, 4).died);
+        assert_eq!(world.player(), (18, 2));
+    }
+
+    #[test]
+    fn reaching_the_goal_starts_the_next_level() {
+        let first = level((10, 2), (12, 2), &[], "");
+        let second = level((4, 2), (6, 2), &[(9, 5)], "");
+        let mut world = GridWorld::new(vec![first, second.clone()]);
+        assert_eq!(run(&mut world, RIGHT, 1), Events::default());
+        let events = world.step(RIGHT);
+        assert!(events.won && events.level_changed && !events.died);
+        assert_eq!(world.level_index(), 1);
+        assert_eq!(world.player(), (4, 2));
+        assert_eq!(world.collisions(), &second.collisions(0));
+    }
+
+    #[test]
+    fn winning_the_last_level_finishes_the_game() {
+        let mut world = GridWorld::new(vec![level((10, 2), (11, 2), &[], "")]);
+        let events = world.step(RIGHT);
+        assert!(events.won && !events.level_changed);
+        assert!(world.is_finished());
+        assert!(world.level().is_none());
+        assert_eq!(world.player(), (11, 2));
+    }
+
+    #[test]
+    fn platforms_move_and_restart_on_death() {
+        let mut world = GridWorld::new(vec![level((10, 2), (17, 2), &[], "platform 5 8 2 1 1 0 3")]);
+        run(&mut world, Input::default(), 2);
+        assert!(world.collisions().is_solid(6, 8) && world.collisions().is_solid(7, 8));
+        world.step(Input::default());
+        assert!(world.collisions().is_solid(8, 8) && !world.collisions().is_solid(6, 8));
+
+        let mut world = GridWorld::new(vec![level((0, 2), (17, 2), &[], "platform 5 8 2 1 1 0 3")]);
+        run(&mut world, Input::default(), 2);
+        let events = world.step(Input {
+            left: true,
+            ..Default::default()
+        });
+        assert!(!events.died);
+        let events = world.step(Input::default());
+        assert!(events.died);
+        assert!(world.collisions().is_solid(5, 8) && world.collisions().is_solid(6, 8));
+    }
+}